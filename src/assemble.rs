@@ -0,0 +1,169 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use regex::Regex;
+use rsx_parser::parse as parse_rsx;
+
+use css;
+use template::{self, Attribute, AttributeValue, Element, Node};
+
+// Passes can make an attribute or a child conditional, with a `GUARD` marker
+// attribute before the attribute, or by wrapping the child in a `GUARDED`
+// element. Guards hold the start of the statement adding the item, such as
+// `#[cfg(...)]` or `if let Some(style) = ...`.
+//
+// The parser only builds nodes from static lists of attributes and children,
+// so an element with guarded items is lowered into a code block building the
+// same `DOMNode` from lists filled at runtime. Each item is converted by the
+// parser on its own, and pushed to its list under its guard.
+
+pub const GUARD: &str = "#[guard]";
+
+const GUARDED: &str = "__rsx_guarded";
+
+pub fn guarded(guard: &str, node: Node) -> Node {
+    Node::Element(Element {
+        name: GUARDED.to_string(),
+        attributes: vec![Attribute::Named(GUARD.to_string(), Some(AttributeValue::Block(guard.to_string())))],
+        children: vec![node],
+        self_closing: false
+    })
+}
+
+pub fn is_guarded(node: &Node) -> bool {
+    unguarded(node).is_some()
+}
+
+// Returns an expression building a fragment of the given children, for the
+// passes lowering elements that take their children as a tree.
+pub fn fragment_of(children: Vec<Node>) -> String {
+    let fragment = match template::fragment(children) {
+        Node::Element(fragment) => fragment,
+        _ => unreachable!()
+    };
+    match lower(fragment) {
+        Node::Block(code) => format!("{{ {} }}", code),
+        node => format!("fragment! {{ {} }}", template::to_source(&[node]))
+    }
+}
+
+pub fn lower(element: Element) -> Node {
+    let is_guard = |attribute: &Attribute| match *attribute {
+        Attribute::Named(ref name, _) => name == GUARD,
+        _ => false
+    };
+    if !element.attributes.iter().any(&is_guard) && !element.children.iter().any(is_guarded) {
+        return Node::Element(element);
+    }
+
+    let mut statements = "let mut __rsx_attributes = vec![]; let mut __rsx_children = vec![]; ".to_string();
+    let mut guard = None;
+
+    for attribute in &element.attributes {
+        match *attribute {
+            Attribute::Named(ref name, Some(AttributeValue::Block(ref code))) if name == GUARD => guard = Some(code.clone()),
+            _ => {
+                let converted = convert(&Element {
+                    attributes: vec![attribute.clone()],
+                    children: vec![],
+                    self_closing: true,
+                    ..element.clone()
+                });
+                push(&mut statements, guard.take(), "__rsx_attributes", list(&converted, 0));
+            }
+        }
+    }
+    if guard.is_some() {
+        panic!("Expected an attribute after a guard on `<{}>`", element.name);
+    }
+
+    for child in &element.children {
+        let (guard, child) = match unguarded(child) {
+            Some((guard, child)) => (Some(guard.clone()), child),
+            None => (None, child)
+        };
+        let converted = convert(&Element {
+            attributes: vec![],
+            children: vec![child.clone()],
+            self_closing: false,
+            ..element.clone()
+        });
+        push(&mut statements, guard, "__rsx_children", list(&converted, 1));
+    }
+
+    let shell = convert(&Element {
+        attributes: vec![],
+        children: vec![],
+        self_closing: true,
+        ..element.clone()
+    });
+    let found = lists(&shell);
+    let (attributes, children) = (found[0], found[1]);
+
+    Node::Block(format!(
+        "{} fragment! {{ {}__rsx_attributes{}__rsx_children{} }}",
+        statements,
+        &shell[..attributes.0],
+        &shell[attributes.1 + 1..children.0],
+        &shell[children.1 + 1..]
+    ))
+}
+
+fn unguarded(node: &Node) -> Option<(&String, &Node)> {
+    match *node {
+        Node::Element(ref element) if element.name == GUARDED => match element.attributes[0] {
+            Attribute::Named(_, Some(AttributeValue::Block(ref guard))) => Some((guard, &element.children[0])),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+fn convert(element: &Element) -> String {
+    let source = template::to_source(&[Node::Element(element.clone())]);
+    let (ast, _) = parse_rsx(&source).unwrap();
+    let converted = quote! { #ast };
+    converted.as_str().to_string()
+}
+
+fn push(statements: &mut String, guard: Option<String>, list: &str, items: &str) {
+    if items.is_empty() {
+        return;
+    }
+    statements.push_str(&format!("{} {{ {}.extend(vec![{}]); }} ", guard.unwrap_or_default(), list, items));
+}
+
+// Returns the items of the attributes (0) or children (1) list of a node.
+fn list(source: &str, index: usize) -> &str {
+    let (open, close) = lists(source)[index];
+    let items = source[open..close].trim();
+    items[items.find('[').unwrap() + 1..].trim().trim_right_matches(',')
+}
+
+// Returns the `vec![..]` lists the parser built a node from, as the offsets of
+// each `vec` and its closing bracket.
+fn lists(source: &str) -> Vec<(usize, usize)> {
+    let re_vec = Regex::new(r"\bvec\s*!\s*\[").unwrap();
+    let mut lists = vec![];
+    let mut pos = 0;
+
+    while let Some(found) = re_vec.find(&source[pos..]) {
+        let open = pos + found.end() - 1;
+        let close = css::matching_brace(source, open).expect("Unbalanced brackets in the parsed element");
+        lists.push((pos + found.start(), close));
+        pos = close + 1;
+    }
+
+    if lists.len() < 2 {
+        panic!("Expected the attributes and children of an element in `{}`", source);
+    }
+    lists
+}
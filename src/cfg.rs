@@ -0,0 +1,133 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use assemble::{self, GUARD};
+use template::{self, Attribute, AttributeValue, Element, Node, CFG, CFG_MARKER};
+
+// Gated attributes and children are turned into items guarded by `#[cfg]`,
+// so that each one is compiled in or out on its own when the element is
+// built. A gated element is left out of its parent's children when its
+// predicate doesn't hold, so its parent is the one adding the guard. At the
+// root of a template, it compiles to an empty fragment instead, since there
+// has to be a tree to return.
+
+// Marks a lowered element with its own predicates until its parent is lowered.
+const GATED_ELEMENT: &str = "__rsx_cfg";
+
+pub fn lower(element: Element, lower_variant: &Fn(Element) -> Node) -> Node {
+    let mut element_predicates = vec![];
+    let mut attributes = vec![];
+    let mut pending = None;
+
+    for attribute in element.attributes.iter().cloned() {
+        match predicate(&attribute) {
+            Some((ref name, ref predicate)) if name == CFG => element_predicates.push(predicate.clone()),
+            Some((_, predicate)) => pending = Some(predicate),
+            None => {
+                if let Some(predicate) = pending.take() {
+                    attributes.push(Attribute::Named(GUARD.to_string(), Some(AttributeValue::Block(guard(&predicate)))));
+                }
+                attributes.push(attribute);
+            }
+        }
+    }
+
+    if pending.is_some() {
+        panic!("Expected an attribute after `#[cfg(...)]` on `<{}>`", element.name);
+    }
+
+    let children = element
+        .children
+        .iter()
+        .map(|child| match gated_element(child) {
+            Some((predicate, node)) => assemble::guarded(&guard(predicate), node.clone()),
+            None => child.clone()
+        })
+        .collect();
+
+    let lowered = lower_variant(Element {
+        attributes,
+        children,
+        ..element
+    });
+
+    if element_predicates.is_empty() {
+        return lowered;
+    }
+    Node::Element(Element {
+        name: GATED_ELEMENT.to_string(),
+        attributes: vec![
+            Attribute::Named(CFG.to_string(), Some(AttributeValue::Block(format!("all({})", element_predicates.join(", "))))),
+        ],
+        children: vec![lowered],
+        self_closing: false
+    })
+}
+
+// Lowers the gated elements left at the root of a template.
+pub fn lower_root(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        let lowered = match gated_element(node) {
+            Some((predicate, lowered)) => {
+                let mut statements = String::new();
+                push_statement(&mut statements, &[predicate.clone()], lowered.clone());
+                push_statement(&mut statements, &[format!("not({})", predicate)], template::fragment(vec![]));
+                Some(Node::Block(format!("{} __rsx_cfg", statements)))
+            }
+            None => None
+        };
+        if let Some(lowered) = lowered {
+            *node = lowered;
+        }
+    }
+}
+
+fn gated_element(node: &Node) -> Option<(&String, &Node)> {
+    match *node {
+        Node::Element(ref element) if element.name == GATED_ELEMENT => match element.attributes[0] {
+            Attribute::Named(_, Some(AttributeValue::Block(ref predicate))) => Some((predicate, &element.children[0])),
+            _ => None
+        },
+        _ => None
+    }
+}
+
+pub fn has_markers(nodes: &[Node]) -> bool {
+    nodes.iter().any(|node| match *node {
+        Node::Element(ref element) => {
+            element.attributes.iter().any(|attribute| predicate(attribute).is_some()) || has_markers(&element.children)
+        }
+        _ => false
+    })
+}
+
+fn predicate(attribute: &Attribute) -> Option<(String, String)> {
+    match *attribute {
+        Attribute::Named(ref name, Some(AttributeValue::Block(ref predicate))) if name == CFG || name == CFG_MARKER => {
+            Some((name.clone(), predicate.clone()))
+        }
+        _ => None
+    }
+}
+
+fn push_statement(statements: &mut String, conditions: &[String], node: Node) {
+    let mut nodes = vec![node];
+    template::wrap_root_blocks(&mut nodes);
+    statements.push_str(&format!(
+        "#[cfg(all({}))] let __rsx_cfg = fragment! {{ {} }}; ",
+        conditions.join(", "),
+        template::to_source(&nodes)
+    ));
+}
+
+fn guard(predicate: &str) -> String {
+    format!("#[cfg({})]", predicate)
+}
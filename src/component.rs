@@ -0,0 +1,235 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::{Ident, Tokens};
+use regex::Regex;
+
+use assemble::{self, GUARD};
+use css::matching_brace;
+use elements::split_fields;
+use template::{Attribute, AttributeValue, Element, Node};
+
+// Elements given type arguments, such as `<List<Item> items={items} />`, are
+// generic components rather than DOM elements. They're built as a struct whose
+// fields are the attributes, plus a `children` fragment when there are any,
+// and turned into a tree by calling the struct's `render(self)` method. `_`
+// can be used for type arguments that should be inferred.
+//
+// A `#[component(memo)]` function keeps the arguments and output of its last
+// few calls in a thread local, and returns a clone of the matching output
+// instead of rebuilding it when called again with equal arguments. The oldest
+// call is forgotten once `MEMO_SIZE` are kept. Arguments are
+// compared with `PartialEq`, reference arguments being stored as their
+// `ToOwned` counterpart, and the output needs to implement `Clone`.
+//
+// `#[component(hydrate)]` is handled by the hydrate module.
+
+const MEMO_SIZE: usize = 8;
+
+pub struct Component {
+    prefix: String,
+    name: String,
+    arguments: Vec<(String, String)>,
+    output: String,
+    body: String
+}
+
+pub fn parse_options(source: &str) -> Vec<String> {
+    let source = source.trim();
+    let source = if source.starts_with('(') && source.ends_with(')') {
+        &source[1..source.len() - 1]
+    } else {
+        source
+    };
+    split_fields(source)
+        .into_iter()
+        .map(|option| match option.as_str() {
+            "memo" | "hydrate" => option,
+            _ => panic!("Unknown component option `{}`", option)
+        })
+        .collect()
+}
+
+pub fn parse_component(source: &str) -> Component {
+    let re_fn = Regex::new(r"(?:^|\s)fn\s+(?P<name>[a-zA-Z_][a-zA-Z0-9_]*)\s*(?P<generics><)?").unwrap();
+    let captures = re_fn.captures(source).expect("Components should be functions");
+    if captures.name("generics").is_some() {
+        panic!("Memoized components can't be generic");
+    }
+
+    let name = captures["name"].to_string();
+    let prefix = source[..captures.get(0).unwrap().start()].to_string();
+
+    let open = captures.get(0).unwrap().end() + source[captures.get(0).unwrap().end()..].find('(').unwrap();
+    let close = matching_brace(source, open).expect("Unbalanced parentheses in component arguments");
+    let arguments = split_fields(&source[open + 1..close])
+        .into_iter()
+        .map(|argument| {
+            let colon = argument.find(':').expect(&format!("Expected `name: Type` but found `{}`", argument));
+            let name = argument[..colon].trim().to_string();
+            if name.is_empty() || name == "self" || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+                panic!("Memoized components only take named arguments, found `{}`", argument);
+            }
+            (name, argument[colon + 1..].trim().to_string())
+        })
+        .collect();
+
+    let rest = &source[close + 1..];
+    let body_start = rest.find('{').expect("Components should have a body");
+    let output = rest[..body_start].trim();
+    if !output.starts_with("->") {
+        panic!("Components should return the tree they build");
+    }
+    if output.contains(" where ") {
+        panic!("Memoized components can't have where clauses");
+    }
+
+    Component {
+        prefix,
+        name,
+        arguments,
+        output: output[2..].trim().to_string(),
+        body: rest[body_start..].to_string()
+    }
+}
+
+pub fn expand_memo(component: &Component) -> Tokens {
+    let prefix = Ident::new(component.prefix.as_str());
+    let name = Ident::new(component.name.as_str());
+    let output = Ident::new(component.output.as_str());
+    let body = Ident::new(component.body.as_str());
+
+    let mut arguments = vec![];
+    let mut names = vec![];
+    let mut key_types = vec![];
+    let mut keys = vec![];
+    let mut comparisons = vec![];
+
+    for (index, &(ref argument, ref ty)) in component.arguments.iter().enumerate() {
+        let argument = Ident::new(argument.as_str());
+        let index = Ident::new(index.to_string());
+        arguments.push(Ident::new(format!("{}: {}", argument, ty)));
+        names.push(argument.clone());
+
+        match borrowed_type(ty) {
+            Some(borrowed) => {
+                let borrowed = Ident::new(borrowed);
+                key_types.push(quote! { <#borrowed as ::std::borrow::ToOwned>::Owned });
+                keys.push(quote! { ::std::borrow::ToOwned::to_owned(#argument) });
+                comparisons.push(quote! { ::std::borrow::Borrow::<#borrowed>::borrow(&key.#index) == #argument });
+            }
+            None => {
+                let ty = Ident::new(ty.as_str());
+                key_types.push(quote! { #ty });
+                keys.push(quote! { ::std::clone::Clone::clone(&#argument) });
+                comparisons.push(quote! { key.#index == #argument });
+            }
+        }
+    }
+
+    let inner_arguments = arguments.clone();
+
+    quote! {
+        #prefix fn #name(#(#arguments),*) -> #output {
+            fn __rsx_component(#(#inner_arguments),*) -> #output #body
+
+            thread_local! {
+                static __RSX_MEMO: ::std::cell::RefCell<Vec<((#(#key_types,)*), #output)>> =
+                    ::std::cell::RefCell::new(Vec::new());
+            }
+
+            let cached = __RSX_MEMO.with(|memo| {
+                memo.borrow()
+                    .iter()
+                    .find(|&&(ref key, _)| true #(&& #comparisons)*)
+                    .map(|&(_, ref output)| ::std::clone::Clone::clone(output))
+            });
+            if let Some(output) = cached {
+                return output;
+            }
+
+            let key = (#(#keys,)*);
+            let output = __rsx_component(#(#names),*);
+            __RSX_MEMO.with(|memo| {
+                let mut memo = memo.borrow_mut();
+                if memo.len() == #MEMO_SIZE {
+                    memo.remove(0);
+                }
+                memo.push((key, ::std::clone::Clone::clone(&output)));
+            });
+            output
+        }
+    }
+}
+
+// Returns `T` for `&T` and `&'a T`. Mutable references can't be memoized.
+fn borrowed_type(ty: &str) -> Option<String> {
+    let ty = ty.trim();
+    if !ty.starts_with('&') {
+        return None;
+    }
+
+    let mut borrowed = ty[1..].trim();
+    if borrowed.starts_with('\'') {
+        let end = borrowed.find(char::is_whitespace).expect(&format!("Expected a type after the lifetime in `{}`", ty));
+        borrowed = borrowed[end..].trim();
+    }
+    if borrowed.starts_with("mut ") {
+        panic!("Memoized components can't take mutable references, found `{}`", ty);
+    }
+
+    Some(borrowed.to_string())
+}
+
+pub fn lower(element: Element) -> Node {
+    if !element.name.contains('<') {
+        return Node::Element(element);
+    }
+
+    let arguments = &element.name[element.name.find('<').unwrap()..];
+    let mut fields = vec![];
+    let mut spread = None;
+    let mut guard = String::new();
+
+    for attribute in &element.attributes {
+        match *attribute {
+            // Gated attributes are gated fields.
+            Attribute::Named(ref name, Some(AttributeValue::Block(ref code))) if name == GUARD => guard = code.clone(),
+            Attribute::Named(ref name, ref value) => {
+                let value = match *value {
+                    Some(AttributeValue::Str(ref code)) | Some(AttributeValue::Literal(ref code)) => code.clone(),
+                    Some(AttributeValue::Block(ref code)) => format!("{{ {} }}", code),
+                    None => "true".to_string()
+                };
+                fields.push(format!("{} {}: {}", guard, name.replace("-", "_"), value));
+                guard.clear();
+            }
+            Attribute::Spread(ref code) => {
+                if spread.is_some() {
+                    panic!("Only one spread attribute is supported on `<{}>`", element.name);
+                }
+                spread = Some(format!("..{{ {} }}", code));
+            }
+        }
+    }
+
+    if !element.children.is_empty() {
+        fields.push(format!("children: {}", assemble::fragment_of(element.children.clone())));
+    }
+    fields.extend(spread);
+
+    Node::Block(format!(
+        "({}::{} {{ {} }}).render()",
+        element.base_name(),
+        arguments,
+        fields.join(", ")
+    ))
+}
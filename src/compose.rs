@@ -0,0 +1,153 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use template::{self, Attribute, AttributeValue, Node};
+
+// Array values on `class` and `style` attributes are composed into a single
+// value. Items can be optional, so that `None` items are skipped. Class names
+// are joined with spaces, and style declarations are merged in order, later
+// declarations replacing earlier ones for the same property.
+
+const CLASS_LIST: &str = r#"
+    trait __RsxClass {
+        fn push_to(self, classes: &mut Vec<String>);
+    }
+    impl<'a> __RsxClass for &'a str {
+        fn push_to(self, classes: &mut Vec<String>) {
+            classes.extend(self.split_whitespace().map(String::from));
+        }
+    }
+    impl __RsxClass for String {
+        fn push_to(self, classes: &mut Vec<String>) {
+            self.as_str().push_to(classes);
+        }
+    }
+    impl<T: __RsxClass> __RsxClass for Option<T> {
+        fn push_to(self, classes: &mut Vec<String>) {
+            if let Some(class) = self {
+                class.push_to(classes);
+            }
+        }
+    }
+    let mut __rsx_classes = vec![];
+"#;
+
+// The stylesheet types are named through `paths::STYLESHEET_TYPES`, qualified once
+// the whole template is expanded. Declarations replace each other when they're
+// the same variant of the same kind of style.
+const STYLE_LIST: &str = r#"
+    trait __RsxStyle {
+        fn push_to(self, declarations: &mut Vec<__rsx_stylesheet_types::StyleDeclaration>);
+    }
+    impl __RsxStyle for __rsx_stylesheet_types::StyleDeclarations {
+        fn push_to(self, declarations: &mut Vec<__rsx_stylesheet_types::StyleDeclaration>) {
+            declarations.extend(self.0.iter().cloned());
+        }
+    }
+    impl<T: __RsxStyle> __RsxStyle for Option<T> {
+        fn push_to(self, declarations: &mut Vec<__rsx_stylesheet_types::StyleDeclaration>) {
+            if let Some(style) = self {
+                style.push_to(declarations);
+            }
+        }
+    }
+    #[derive(PartialEq)]
+    enum __RsxProperty {
+        Layout(::std::mem::Discriminant<__rsx_stylesheet_types::FlexStyle>),
+        Theme(::std::mem::Discriminant<__rsx_stylesheet_types::ThemeStyle>),
+        Other(::std::mem::Discriminant<__rsx_stylesheet_types::StyleDeclaration>)
+    }
+    #[allow(unreachable_patterns)]
+    fn __rsx_property(declaration: &__rsx_stylesheet_types::StyleDeclaration) -> __RsxProperty {
+        match *declaration {
+            __rsx_stylesheet_types::StyleDeclaration::Layout(ref style) => __RsxProperty::Layout(::std::mem::discriminant(style)),
+            __rsx_stylesheet_types::StyleDeclaration::Theme(ref style) => __RsxProperty::Theme(::std::mem::discriminant(style)),
+            _ => __RsxProperty::Other(::std::mem::discriminant(declaration))
+        }
+    }
+    let mut __rsx_declarations = vec![];
+"#;
+
+pub fn compose_attributes(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Node::Element(ref mut element) = *node {
+            for attribute in element.attributes.iter_mut() {
+                let composed = match *attribute {
+                    Attribute::Named(ref name, Some(AttributeValue::Block(ref code))) => match array_items(code) {
+                        Some(items) => match name.as_str() {
+                            "class" => Some(class_list(&items)),
+                            "style" => Some(style_list(&items)),
+                            _ => None
+                        },
+                        None => None
+                    },
+                    _ => None
+                };
+                if let Some(composed) = composed {
+                    if let Attribute::Named(_, ref mut value) = *attribute {
+                        *value = Some(AttributeValue::Block(composed));
+                    }
+                }
+            }
+            compose_attributes(&mut element.children);
+        }
+    }
+}
+
+fn class_list(items: &[String]) -> String {
+    let mut code = CLASS_LIST.to_string();
+    for item in items {
+        code.push_str(&format!("__RsxClass::push_to({}, &mut __rsx_classes); ", item));
+    }
+    code.push_str("__rsx_classes.join(\" \")");
+    code
+}
+
+fn style_list(items: &[String]) -> String {
+    let mut code = STYLE_LIST.to_string();
+    for item in items {
+        code.push_str(&format!("__RsxStyle::push_to({}, &mut __rsx_declarations); ", item));
+    }
+    code.push_str(
+        "let mut __rsx_merged: Vec<__rsx_stylesheet_types::StyleDeclaration> = vec![]; \
+         for declaration in __rsx_declarations { \
+             let property = __rsx_property(&declaration); \
+             __rsx_merged.retain(|existing| __rsx_property(existing) != property); \
+             __rsx_merged.push(declaration); \
+         } \
+         __rsx_stylesheet_types::StyleDeclarations(__rsx_stylesheet_types::InlineDeclarations::from_vec(__rsx_merged))"
+    );
+    code
+}
+
+// Returns the items of a `[a, b, ...]` array expression.
+fn array_items(code: &str) -> Option<Vec<String>> {
+    let code = code.trim();
+    if !code.starts_with('[') || !code.ends_with(']') {
+        return None;
+    }
+
+    let mut depth = 0;
+    for (index, c) in code.char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => {
+                depth -= 1;
+                if depth == 0 && index != code.len() - 1 {
+                    return None;
+                }
+            }
+            _ => {}
+        }
+    }
+
+    Some(template::split_top_level(&code[1..code.len() - 1]))
+}
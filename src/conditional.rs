@@ -0,0 +1,179 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::Tokens;
+use regex::Regex;
+use rsx_stylesheet::types::Stylesheet;
+
+use css;
+use media;
+use states;
+
+// A stylesheet with `@media` rules or interactive states is split into runs
+// of rules sharing the same media condition and required states, each
+// converted at compile time. The expansion keeps them in order along with
+// their conditions in a `ConditionalStylesheet`, so `evaluate(viewport)`
+// builds the stylesheet applying to a viewport size at runtime, and
+// `declarations_for(selector, state)` the declarations of a node in the given
+// states. Proc macro crates can't export runtime items, so that type is
+// generated once per crate in the `rsx_context` module, and names the
+// stylesheet types with `paths::STYLESHEET_TYPES`.
+
+pub const RUNTIME: &str = r#"
+    pub type ConditionalRules = (
+        Option<fn((f32, f32)) -> bool>,
+        u32,
+        fn() -> Vec<__rsx_stylesheet_types::StyleRule>
+    );
+
+    #[derive(Clone)]
+    pub struct ConditionalStylesheet {
+        rules: Vec<ConditionalRules>
+    }
+
+    impl ConditionalStylesheet {
+        pub fn new(rules: Vec<ConditionalRules>) -> Self {
+            ConditionalStylesheet { rules }
+        }
+
+        pub fn evaluate(&self, viewport: (f32, f32)) -> __rsx_stylesheet_types::Stylesheet {
+            let rules = self.rules
+                .iter()
+                .filter(|&&(media, required, _)| applies(media, required, Some(viewport), 0))
+                .flat_map(|&(_, _, rules)| rules())
+                .collect();
+            __rsx_stylesheet_types::Stylesheet::from(__rsx_stylesheet_types::InlineRules::from_vec(rules))
+        }
+
+        pub fn declarations_for(&self, selector: &str, state: u32) -> __rsx_stylesheet_types::StyleDeclarations {
+            self.declarations(selector, state, None)
+        }
+
+        pub fn declarations_at(&self, selector: &str, state: u32, viewport: (f32, f32)) -> __rsx_stylesheet_types::StyleDeclarations {
+            self.declarations(selector, state, Some(viewport))
+        }
+
+        // Same as `Stylesheet::take`, with the rules that apply to a node
+        // without any state or viewport.
+        pub fn take(&mut self, selector: &str) -> __rsx_stylesheet_types::StyleDeclarations {
+            self.declarations(selector, 0, None)
+        }
+
+        fn declarations(&self, selector: &str, state: u32, viewport: Option<(f32, f32)>) -> __rsx_stylesheet_types::StyleDeclarations {
+            let mut declarations = vec![];
+            for &(media, required, rules) in &self.rules {
+                if applies(media, required, viewport, state) {
+                    let rules = __rsx_stylesheet_types::InlineRules::from_vec(rules());
+                    let mut stylesheet = __rsx_stylesheet_types::Stylesheet::from(rules);
+                    declarations.extend(stylesheet.take(selector).0.iter().cloned());
+                }
+            }
+            __rsx_stylesheet_types::StyleDeclarations(__rsx_stylesheet_types::InlineDeclarations::from_vec(declarations))
+        }
+    }
+
+    // Media rules only apply when there's a viewport to evaluate them against.
+    fn applies(media: Option<fn((f32, f32)) -> bool>, required: u32, viewport: Option<(f32, f32)>, state: u32) -> bool {
+        state & required == required && match (media, viewport) {
+            (Some(media), Some(viewport)) => media(viewport),
+            (Some(_), None) => false,
+            (None, _) => true
+        }
+    }
+"#;
+
+pub struct Rule {
+    pub media: Option<String>,
+    pub state: u32,
+    pub css: String
+}
+
+pub fn split(css: &str) -> Vec<Rule> {
+    let mut rules = vec![];
+    split_into(&mut rules, css, None);
+    rules
+}
+
+pub fn is_conditional(rules: &[Rule]) -> bool {
+    rules.iter().any(|rule| rule.media.is_some() || rule.state != 0)
+}
+
+pub fn expand(rules: &[Rule], parse: &Fn(&str) -> Stylesheet) -> Tokens {
+    let entries = rules
+        .iter()
+        .map(|rule| {
+            let media = match rule.media {
+                Some(ref condition) => format!("Some((|__rsx_viewport: (f32, f32)| {}) as fn((f32, f32)) -> bool)", condition),
+                None => "None".to_string()
+            };
+            format!("({}, {}, (|| {}) as fn() -> Vec<StyleRule>)", media, rule.state, style_rules(&parse(&rule.css)))
+        })
+        .collect::<Vec<_>>();
+
+    let mut tokens = Tokens::new();
+    tokens.append(format!("::rsx_context::ConditionalStylesheet::new(vec![{}])", entries.join(", ")));
+    tokens
+}
+
+fn split_into(rules: &mut Vec<Rule>, css: &str, media: Option<&String>) {
+    let mut pos = 0;
+
+    while let Some(open) = css[pos..].find('{').map(|open| pos + open) {
+        let start = css::prelude_start(css, pos, open);
+        let close = css::matching_brace(css, open).expect("Unbalanced braces in stylesheet");
+        let prelude = &css[start..open];
+        let body = &css[open + 1..close];
+        push(rules, media, 0, &css[pos..start]);
+
+        if let Some(condition) = media::condition_of(prelude) {
+            if media.is_some() {
+                panic!("Nested `@media` rules aren't supported");
+            }
+            split_into(rules, body, Some(&condition));
+        } else if prelude.trim().starts_with('@') {
+            push(rules, media, 0, &css[start..close + 1]);
+        } else {
+            for (state, selectors) in states::split_selectors(prelude) {
+                push(rules, media, state, &format!("{} {{{}}}", selectors, body));
+            }
+        }
+        pos = close + 1;
+    }
+
+    push(rules, media, 0, &css[pos..]);
+}
+
+// Consecutive rules under the same conditions share an entry.
+fn push(rules: &mut Vec<Rule>, media: Option<&String>, state: u32, css: &str) {
+    if css.trim().is_empty() {
+        return;
+    }
+    if let Some(last) = rules.last_mut() {
+        if last.media.as_ref() == media && last.state == state {
+            last.css.push_str(css);
+            return;
+        }
+    }
+    rules.push(Rule {
+        media: media.cloned(),
+        state,
+        css: css.to_string()
+    });
+}
+
+// Returns the `vec![..]` of style rules a stylesheet is built from.
+fn style_rules(stylesheet: &Stylesheet) -> String {
+    let source = quote! { #stylesheet }.as_str().to_string();
+    let re_vec = Regex::new(r"\bvec\s*!\s*\[").unwrap();
+    let found = re_vec.find(&source).expect("Expected the rules of the stylesheet");
+    let close = css::matching_brace(&source, found.end() - 1).expect("Unbalanced brackets in stylesheet");
+    source[found.start()..close + 1].to_string()
+}
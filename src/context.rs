@@ -0,0 +1,117 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::Tokens;
+
+use assemble::{self, GUARD};
+use conditional;
+use template::{self, AttributeValue, Element, Node};
+
+// Proc macro crates can't export runtime items, so the context stack lives in
+// a `rsx_context` module generated by `rsx_context!()` at the crate root.
+// A `<Provider value={...}>` element pushes its value on that stack while its
+// children are built, and `use_context::<T>()` returns the innermost value of
+// type `T` provided above the calling component. The module also holds the
+// active locale that translated messages are looked up in, and the type of
+// stylesheets with conditional rules.
+
+pub const PROVIDER: &str = "Provider";
+
+const MODULE: &str = r#"
+    use std::any::Any;
+    use std::cell::RefCell;
+
+    thread_local! {
+        static CONTEXT: RefCell<Vec<Box<Any>>> = RefCell::new(vec![]);
+        static LOCALE: RefCell<Option<String>> = RefCell::new(None);
+    }
+
+    struct Guard;
+
+    impl Drop for Guard {
+        fn drop(&mut self) {
+            CONTEXT.with(|context| context.borrow_mut().pop());
+        }
+    }
+
+    pub fn provide<T: 'static, R, F: FnOnce() -> R>(value: T, build: F) -> R {
+        CONTEXT.with(|context| context.borrow_mut().push(Box::new(value)));
+        let _guard = Guard;
+        build()
+    }
+
+    pub fn use_context<T: Clone + 'static>() -> Option<T> {
+        CONTEXT.with(|context| {
+            context
+                .borrow()
+                .iter()
+                .rev()
+                .filter_map(|value| value.downcast_ref::<T>())
+                .next()
+                .cloned()
+        })
+    }
+
+    pub fn set_locale<S: Into<String>>(locale: S) {
+        LOCALE.with(|current| *current.borrow_mut() = Some(locale.into()));
+    }
+
+    pub fn locale() -> Option<String> {
+        LOCALE.with(|current| current.borrow().clone())
+    }
+"#;
+
+pub fn expand_module() -> Tokens {
+    let mut tokens = Tokens::new();
+    tokens.append(format!("pub mod rsx_context {{ {} {} }}", MODULE, conditional::RUNTIME));
+    tokens
+}
+
+// Replaces a `<Provider>` element with a code block building its children
+// inside `rsx_context::provide`. Other elements are kept as is. A single child
+// is built directly, so it takes the provider's place in the tree, but a code
+// block only yields one tree, so several children are put in a fragment.
+pub fn lower(element: Element) -> Node {
+    if element.name != PROVIDER {
+        return Node::Element(element);
+    }
+
+    if element.attribute(GUARD).is_some() {
+        panic!("Conditional attributes aren't supported on `<{}>`", PROVIDER);
+    }
+
+    let value = match element.attribute("value") {
+        Some(&AttributeValue::Block(ref code)) => format!("{{ {} }}", code),
+        Some(&AttributeValue::Str(ref code)) | Some(&AttributeValue::Literal(ref code)) => code.clone(),
+        None => panic!("Expected a `value` attribute on `<{}>`", PROVIDER)
+    };
+
+    let children = element
+        .children
+        .iter()
+        .filter(|child| match **child {
+            Node::Text(ref text) => !text.trim().is_empty(),
+            _ => true
+        })
+        .cloned()
+        .collect::<Vec<_>>();
+
+    let single = match children.first() {
+        Some(&Node::Block(ref code)) if children.len() == 1 => Some(format!("{{ {} }}", code)),
+        Some(child @ &Node::Element(_)) if children.len() == 1 && !assemble::is_guarded(child) => {
+            Some(format!("fragment! {{ {} }}", template::to_source(&children)))
+        }
+        _ => None
+    };
+    let build = single.unwrap_or_else(|| assemble::fragment_of(children));
+
+    Node::Block(format!("::rsx_context::provide({}, || {})", value, build))
+}
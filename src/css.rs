@@ -0,0 +1,559 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+use proc_macro::{Delimiter, Span, TokenNode, TokenStream, TokenTree};
+use regex::Regex;
+use rsx_stylesheet::types::Stylesheet;
+
+// When converting TokenStreams to Strings, a whitespace is inserted between
+// each and every token, turning ".foo" into ". foo" or "margin-left" into
+// "margin - left", which isn't valid CSS. Instead, rebuild the CSS text from
+// the tokens' spans: tokens that were adjacent in the source are written
+// without whitespace between them, and others are separated by a space, such
+// as tokens generated by other macros. Lines are broken after braces and
+// semicolons, and the line each token is written on is kept to report
+// diagnostics on inline CSS at the offending tokens.
+
+const OPTIONS: &[&str] = &["crate", "lint", "typed"];
+
+pub fn source_from_tokens(tokens: TokenStream) -> (String, Vec<(usize, String, Span)>) {
+    let mut source = String::new();
+    let mut spans = vec![];
+    let mut last = None;
+    write_tokens(tokens, &mut source, &mut spans, &mut last);
+    (source, spans)
+}
+
+// Returns the tokens after the leading `crate = path,`, `lint = level,` and
+// `typed = bool,` options, if any.
+pub fn skip_options(tokens: TokenStream) -> TokenStream {
+    let mut trees = tokens.into_iter().collect::<Vec<_>>();
+
+    loop {
+        let is_option = match (trees.get(0).map(|tree| &tree.kind), trees.get(1).map(|tree| &tree.kind)) {
+            (Some(&TokenNode::Term(term)), Some(&TokenNode::Op('=', _))) => OPTIONS.contains(&term.as_str()),
+            _ => false
+        };
+        if !is_option {
+            return trees.into_iter().collect();
+        }
+
+        let comma = trees
+            .iter()
+            .position(|tree| match tree.kind {
+                TokenNode::Op(',', _) => true,
+                _ => false
+            })
+            .expect("Expected a `,` after the option");
+        trees = trees.split_off(comma + 1);
+    }
+}
+
+// Returns the file of some inline CSS.
+pub fn inline_location(tokens: &TokenStream) -> String {
+    let span = match tokens.clone().into_iter().next() {
+        Some(tree) => tree.span,
+        None => Span::call_site()
+    };
+    span.source_file().as_str().to_string()
+}
+
+// Returns the contents of the `{ ... }` group following the given keyword.
+pub fn group_after(tokens: TokenStream, keyword: &str) -> Option<TokenStream> {
+    let mut after_keyword = false;
+
+    for tree in tokens {
+        match tree.kind {
+            TokenNode::Term(term) if term.as_str() == keyword => after_keyword = true,
+            TokenNode::Group(Delimiter::Brace, stream) => {
+                if after_keyword {
+                    return Some(stream);
+                }
+            }
+            _ => after_keyword = false
+        }
+    }
+
+    None
+}
+
+fn write_tokens(tokens: TokenStream, source: &mut String, spans: &mut Vec<(usize, String, Span)>, last: &mut Option<Span>) {
+    for tree in tokens {
+        let span = tree.span;
+        separate(source, last, span);
+
+        match tree.kind {
+            TokenNode::Group(delimiter, stream) => {
+                let (open, close) = match delimiter {
+                    Delimiter::Parenthesis => ("(", ")"),
+                    Delimiter::Brace => ("{", "}"),
+                    Delimiter::Bracket => ("[", "]"),
+                    Delimiter::None => ("", "")
+                };
+                source.push_str(open);
+                *last = None;
+                write_tokens(stream, source, spans, last);
+                if delimiter == Delimiter::Brace && !source.ends_with('\n') {
+                    source.push('\n');
+                }
+                source.push_str(close);
+            }
+            kind => {
+                let text = TokenStream::from(TokenTree { span, kind }).to_string();
+                spans.push((source.matches('\n').count() + 1, text.clone(), span));
+                source.push_str(&text);
+            }
+        }
+
+        if source.ends_with(|c| c == '{' || c == '}' || c == ';') {
+            source.push('\n');
+        }
+        *last = Some(span);
+    }
+}
+
+fn separate(source: &mut String, last: &Option<Span>, span: Span) {
+    match *last {
+        Some(last) if last.end() == span.start() => {}
+        Some(_) if !source.ends_with('\n') => source.push(' '),
+        _ => {}
+    }
+}
+
+// Stylesheets read from files, with their path and contents.
+pub struct Imported {
+    pub path: PathBuf,
+    pub css: String
+}
+
+pub fn read_file(path: &Path) -> String {
+    let mut file_contents = String::new();
+    File::open(path)
+        .expect(&format!("Couldn't open file {}", path.to_string_lossy()))
+        .read_to_string(&mut file_contents)
+        .expect(&format!("Couldn't read file {}", path.to_string_lossy()));
+    file_contents
+}
+
+// Replaces `@import "file.css";` rules with the contents of the imported files,
+// recursively. Imports are relative to the importing file, or resolved like
+// other macro paths for inline CSS. `stack` holds the files being imported, to
+// detect cycles, and every file read is added to `imported`. Comments are
+// stripped first, so that commented out imports are ignored.
+pub fn inline_imports(css: &str, importer: Option<&Path>, stack: &mut Vec<PathBuf>, imported: &mut Vec<Imported>) -> String {
+    let css = &strip_comments(css);
+    let re_import = Regex::new(r#"@import\s+(?:url\(\s*)?(?:"(?P<double>[^"]*)"|'(?P<single>[^']*)')\s*\)?\s*(?P<media>[^;]*);"#).unwrap();
+    let mut inlined = String::new();
+    let mut last = 0;
+
+    for captures in re_import.captures_iter(css) {
+        let whole = captures.get(0).unwrap();
+        let file = captures
+            .name("double")
+            .or_else(|| captures.name("single"))
+            .unwrap()
+            .as_str();
+        if !captures["media"].trim().is_empty() {
+            panic!("Media queries on `@import \"{}\"` aren't supported", file);
+        }
+
+        let path = match importer.and_then(|importer| importer.parent()) {
+            Some(dir) => dir.join(file),
+            None => ::paths::resolve(file)
+        };
+        if !path.exists() {
+            panic!(
+                "Couldn't find file {} imported from {}",
+                path.to_string_lossy(),
+                importer.map_or("inline CSS".to_string(), |importer| importer.to_string_lossy().into_owned())
+            );
+        }
+
+        let canonical = path.canonicalize().unwrap();
+        if stack.contains(&canonical) {
+            let cycle = stack
+                .iter()
+                .chain(Some(&canonical))
+                .map(|path| path.to_string_lossy().into_owned())
+                .collect::<Vec<_>>();
+            panic!("Import cycle between stylesheets: {}", cycle.join(" -> "));
+        }
+
+        let contents = read_file(&path);
+        stack.push(canonical);
+        let nested = inline_imports(&contents, Some(&path), stack, imported);
+        stack.pop();
+        imported.push(Imported { path, css: contents });
+
+        inlined.push_str(&css[last..whole.start()]);
+        inlined.push_str(&nested);
+        last = whole.end();
+    }
+
+    inlined.push_str(&css[last..]);
+    inlined
+}
+
+// The servo parser silently drops the declarations it can't convert into a
+// `StyleDeclaration`, as well as the at-rules it doesn't support. Each
+// declaration is parsed on its own to find out which ones are dropped, and
+// reported depending on the lint level: `allow`, `warn` (the default) or
+// `deny`, given as a `lint = level,` option or with `RSX_CSS_LINT`. They point
+// at the offending tokens for inline CSS, and at the macro invocation with the
+// file and line for stylesheets read from files.
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Level {
+    Allow,
+    Warn,
+    Deny
+}
+
+// At-rules handled before the CSS reaches the servo parser.
+const SUPPORTED_AT_RULES: &[&str] = &["import", "media"];
+
+pub struct Diagnostic {
+    pub line: usize,
+    pub source: String,
+    pub message: String
+}
+
+// Returns the lint level given as `lint = level,` and the remaining input.
+pub fn take_lint_level(source: &str) -> (Level, String) {
+    let re_lint = Regex::new(r"^\s*lint\s*=\s*(?P<level>[a-z]+)\s*,").unwrap();
+    let (level, rest) = match re_lint.captures(source) {
+        Some(captures) => (
+            captures["level"].to_string(),
+            source[captures.get(0).unwrap().end()..].to_string()
+        ),
+        None => (env::var("RSX_CSS_LINT").unwrap_or_default(), source.to_string())
+    };
+
+    let level = match level.as_str() {
+        "allow" => Level::Allow,
+        "warn" | "" => Level::Warn,
+        "deny" => Level::Deny,
+        level => panic!("Unknown lint level `{}`, expected `allow`, `warn` or `deny`", level)
+    };
+    (level, rest)
+}
+
+// Returns the expression given as `option = expr,` and the remaining input.
+pub fn take_expression(source: &str, option: &str) -> (Option<String>, String) {
+    let re_option = Regex::new(&format!(r"^\s*{}\s*=", option)).unwrap();
+    let start = match re_option.find(source) {
+        Some(found) => found.end(),
+        None => return (None, source.to_string())
+    };
+
+    let mut depth = 0;
+    for (index, c) in source[start..].char_indices() {
+        match c {
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                let expression = source[start..start + index].trim().to_string();
+                return (Some(expression), source[start + index + 1..].to_string());
+            }
+            _ => {}
+        }
+    }
+    panic!("Expected a `,` after the `{}` option", option);
+}
+
+pub fn check(css: &str, parse: &Fn(&str) -> Stylesheet) -> Vec<Diagnostic> {
+    let css = strip_comments(css);
+    let empty = parse(".__rsx_lint {}");
+    let mut diagnostics = vec![];
+    let mut pos = 0;
+
+    while pos < css.len() {
+        let rest = &css[pos..];
+        let skipped = rest.len() - rest.trim_left().len();
+        pos += skipped;
+        if pos >= css.len() {
+            break;
+        }
+
+        let rest = &css[pos..];
+        let end = match rest.find(|c: char| c == ';' || c == '{') {
+            Some(index) if rest[index..].starts_with('{') => matching_brace(rest, index).unwrap_or(rest.len() - 1),
+            Some(index) => index,
+            None => rest.len() - 1
+        };
+
+        if rest.starts_with('@') {
+            let name = rest[1..].split(|c: char| !(c.is_alphanumeric() || c == '-')).next().unwrap();
+            if SUPPORTED_AT_RULES.contains(&name) {
+                if let Some(open) = rest[..end].find('{') {
+                    let first_line = line_of(&css, pos + open + 1);
+                    diagnostics.extend(check(&rest[open + 1..end], parse).into_iter().map(|diagnostic| Diagnostic {
+                        line: first_line + diagnostic.line - 1,
+                        ..diagnostic
+                    }));
+                }
+                pos += end + 1;
+                continue;
+            }
+            diagnostics.push(Diagnostic {
+                line: line_of(&css, pos),
+                source: format!("@{}", name),
+                message: format!("Unsupported at-rule `@{}`", name)
+            });
+        } else if let Some(open) = rest[..end].find('{') {
+            let body = &rest[open + 1..end];
+            check_declarations(&css, pos + open + 1, body, &empty, parse, &mut diagnostics);
+        }
+
+        pos += end + 1;
+    }
+
+    diagnostics
+}
+
+// Reports each diagnostic at the first token of its source on its line, or at
+// the macro invocation, prefixed with the file and line, when there's none.
+// Diagnostics on inline CSS are reported at the first token of the offending
+// line, or the last one before it, and others at the call site.
+pub fn report(diagnostics: Vec<Diagnostic>, file: &str, tokens: &[(usize, String, Span)], level: Level) {
+    for diagnostic in diagnostics {
+        let line = diagnostic.line;
+        let first_token = first_token(&diagnostic.source);
+        let on_line = tokens.iter().filter(|&&(token_line, _, _)| token_line == line).collect::<Vec<_>>();
+        let found = on_line
+            .iter()
+            .find(|&&&(_, ref text, _)| *text == first_token)
+            .or_else(|| on_line.first())
+            .cloned()
+            .or_else(|| tokens.iter().rev().find(|&&(token_line, _, _)| token_line < line))
+            .map(|&(_, _, span)| span);
+
+        let (span, message) = match found {
+            Some(span) => (span, diagnostic.message),
+            None => (Span::call_site(), format!("{}:{}: {}", file, line, diagnostic.message))
+        };
+        match level {
+            Level::Allow => {}
+            Level::Warn => span.warning(message).emit(),
+            Level::Deny => span.error(message).emit()
+        }
+    }
+}
+
+fn first_token(source: &str) -> String {
+    let source = source.trim_left();
+    match source.find(|c: char| !(c.is_alphanumeric() || c == '_')) {
+        Some(0) => source.chars().next().unwrap().to_string(),
+        Some(end) => source[..end].to_string(),
+        None => source.to_string()
+    }
+}
+
+fn check_declarations(
+    css: &str,
+    offset: usize,
+    body: &str,
+    empty: &Stylesheet,
+    parse: &Fn(&str) -> Stylesheet,
+    diagnostics: &mut Vec<Diagnostic>
+) {
+    let mut start = 0;
+
+    for (index, c) in body.char_indices().chain(Some((body.len(), ';'))) {
+        if c != ';' {
+            continue;
+        }
+
+        let declaration = body[start..index].trim();
+        let leading = body[start..index].len() - body[start..index].trim_left().len();
+        let line = line_of(css, offset + start + leading);
+        start = index + 1;
+
+        if declaration.is_empty() {
+            continue;
+        }
+        if !declaration.contains(':') {
+            diagnostics.push(Diagnostic {
+                line,
+                source: declaration.to_string(),
+                message: format!("Expected `property: value` but found `{}`", declaration)
+            });
+            continue;
+        }
+        if parse(&format!(".__rsx_lint {{ {} }}", declaration)) == *empty {
+            diagnostics.push(Diagnostic {
+                line,
+                source: declaration.to_string(),
+                message: format!("Unsupported property or invalid value in `{}`", declaration)
+            });
+        }
+    }
+}
+
+// Replaces comments with spaces, keeping line breaks and offsets.
+pub fn strip_comments(css: &str) -> String {
+    let mut stripped = String::new();
+    let mut rest = css;
+
+    while let Some(start) = rest.find("/*") {
+        stripped.push_str(&rest[..start]);
+        let end = rest[start + 2..].find("*/").map_or(rest.len(), |end| start + end + 4);
+        stripped.extend(rest[start..end].chars().map(|c| if c == '\n' { '\n' } else { ' ' }));
+        rest = &rest[end..];
+    }
+
+    stripped.push_str(rest);
+    stripped
+}
+
+fn line_of(css: &str, offset: usize) -> usize {
+    css[..offset].matches('\n').count() + 1
+}
+
+// Returns the offset of the bracket closing the one at `open`, skipping string
+// literals. Used by every pass walking the rules of a stylesheet.
+pub fn matching_brace(source: &str, open: usize) -> Option<usize> {
+    let mut depth = 0;
+    let mut quoted = false;
+    let mut escaped = false;
+
+    for (index, c) in source[open..].char_indices() {
+        if quoted {
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                quoted = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => quoted = true,
+            '{' | '(' | '[' => depth += 1,
+            '}' | ')' | ']' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(open + index);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// Returns the start of the prelude of the rule opened at `open`, right after
+// the statement or rule before it, but not before `from`.
+pub fn prelude_start(css: &str, from: usize, open: usize) -> usize {
+    css[from..open]
+        .rfind(|c: char| c == ';' || c == '{' || c == '}')
+        .map_or(from, |end| from + end + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::env;
+    use std::fs::{self, File};
+    use std::io::Write;
+    use std::path::PathBuf;
+
+    use super::{check, inline_imports, read_file};
+    use parse_stylesheet;
+
+    fn write_files(dir: &str, files: &[(&str, &str)]) -> PathBuf {
+        let dir = env::temp_dir().join(dir);
+        fs::create_dir_all(&dir).unwrap();
+        for &(name, css) in files {
+            File::create(dir.join(name)).unwrap().write_all(css.as_bytes()).unwrap();
+        }
+        dir
+    }
+
+    fn inline_file(path: PathBuf) -> (String, usize) {
+        let css = read_file(&path);
+        let mut stack = vec![path.canonicalize().unwrap()];
+        let mut imported = vec![];
+        let inlined = inline_imports(&css, Some(&path), &mut stack, &mut imported);
+        (inlined, imported.len())
+    }
+
+    #[test]
+    fn test_inline_imports() {
+        let dir = write_files(
+            "rsx-css-imports",
+            &[
+                ("a.css", "/* @import \"missing.css\"; */\n@import \"b.css\";\n.a { width: 1px; }"),
+                ("b.css", ".b { width: 2px; }")
+            ]
+        );
+
+        let (inlined, imported) = inline_file(dir.join("a.css"));
+        assert!(inlined.contains(".b { width: 2px; }"));
+        assert!(!inlined.contains("missing.css"));
+        assert_eq!(imported, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "Import cycle between stylesheets")]
+    fn test_inline_imports_cycle() {
+        let dir = write_files(
+            "rsx-css-import-cycle",
+            &[("a.css", "@import \"b.css\";"), ("b.css", "@import \"a.css\";")]
+        );
+        inline_file(dir.join("a.css"));
+    }
+
+    #[test]
+    #[should_panic(expected = "Couldn't find file")]
+    fn test_inline_imports_missing_file() {
+        let dir = write_files("rsx-css-import-missing", &[("a.css", "@import \"missing.css\";")]);
+        inline_file(dir.join("a.css"));
+    }
+
+    #[test]
+    fn test_check() {
+        let css = ".foo {
+    unknown-property: 1px;
+    width: 10px;
+}
+@font-face {
+}
+.bar { color }
+@media (min-width: 100px) {
+    .baz { bogus: 1; }
+}";
+
+        let diagnostics = check(css, &parse_stylesheet)
+            .into_iter()
+            .map(|diagnostic| (diagnostic.line, diagnostic.source, diagnostic.message))
+            .collect::<Vec<_>>();
+
+        assert_eq!(
+            diagnostics,
+            vec![
+                (
+                    2,
+                    "unknown-property: 1px".to_string(),
+                    "Unsupported property or invalid value in `unknown-property: 1px`".to_string()
+                ),
+                (5, "@font-face".to_string(), "Unsupported at-rule `@font-face`".to_string()),
+                (7, "color".to_string(), "Expected `property: value` but found `color`".to_string()),
+                (9, "bogus: 1".to_string(), "Unsupported property or invalid value in `bogus: 1`".to_string())
+            ]
+        );
+    }
+}
@@ -0,0 +1,171 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::collections::hash_map::DefaultHasher;
+use std::env;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::Write;
+use std::path::PathBuf;
+
+use proc_macro::Span;
+use quote::Tokens;
+
+// When `RSX_DEBUG_EXPANSION` is set, writes the expansion of a macro to
+// `target/rsx-expanded/<file>-<macro>-<hash>.rs`, where the hash is that of
+// the expanded code, so that invocations in the same file don't collide.
+pub fn dump(macro_name: &str, expanded: &Tokens) {
+    match env::var("RSX_DEBUG_EXPANSION") {
+        Ok(ref value) if !value.is_empty() && value != "0" => {}
+        _ => return
+    }
+
+    let dir = expansion_dir();
+    fs::create_dir_all(&dir).expect(&format!(
+        "Couldn't create directory {}",
+        dir.to_string_lossy()
+    ));
+
+    let mut hasher = DefaultHasher::new();
+    expanded.as_str().hash(&mut hasher);
+
+    let file_path = dir.join(format!("{}-{}-{:x}.rs", call_site_file(), macro_name, hasher.finish()));
+    File::create(&file_path)
+        .expect(&format!(
+            "Couldn't create file {}",
+            file_path.to_string_lossy()
+        ))
+        .write_all(pretty_print(expanded.as_str()).as_bytes())
+        .expect(&format!(
+            "Couldn't write file {}",
+            file_path.to_string_lossy()
+        ));
+}
+
+fn expansion_dir() -> PathBuf {
+    let target = match env::var("CARGO_TARGET_DIR") {
+        Ok(dir) => PathBuf::from(dir),
+        Err(_) => env::current_dir().unwrap().join("target")
+    };
+    target.join("rsx-expanded")
+}
+
+fn call_site_file() -> String {
+    Span::call_site()
+        .source_file()
+        .as_str()
+        .chars()
+        .map(|c| if c.is_alphanumeric() || c == '-' { c } else { '_' })
+        .collect::<String>()
+        .trim_matches('_')
+        .to_string()
+}
+
+// Token streams are stringified on a single line, so break lines after
+// braces and semicolons to make the output readable.
+fn pretty_print(source: &str) -> String {
+    let mut printed = String::new();
+    let mut indent: usize = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut line_start = true;
+    let mut chars = source.chars();
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            printed.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        if line_start {
+            if c == ' ' {
+                continue;
+            }
+            if c == '}' {
+                indent = indent.saturating_sub(1);
+            }
+            printed.push_str(&"    ".repeat(indent));
+            line_start = false;
+        } else if c == '}' {
+            indent = indent.saturating_sub(1);
+            printed.push('\n');
+            printed.push_str(&"    ".repeat(indent));
+        }
+
+        printed.push(c);
+
+        // Copy char literals such as `'}'` as they are, but not lifetimes.
+        if c == '\'' {
+            let rest = chars.clone().take(3).collect::<String>();
+            if rest.starts_with('\\') || rest.chars().nth(1) == Some('\'') {
+                let mut escaped = false;
+                while let Some(c) = chars.next() {
+                    printed.push(c);
+                    if escaped {
+                        escaped = false;
+                    } else if c == '\\' {
+                        escaped = true;
+                    } else if c == '\'' {
+                        break;
+                    }
+                }
+            }
+            continue;
+        }
+
+        match c {
+            '"' => in_string = true,
+            '{' => {
+                indent += 1;
+                printed.push('\n');
+                line_start = true;
+            }
+            '}' | ';' => {
+                printed.push('\n');
+                line_start = true;
+            }
+            _ => {}
+        }
+    }
+
+    printed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::pretty_print;
+
+    #[test]
+    fn test_pretty_print_blocks() {
+        assert_eq!(pretty_print("fn f ( ) { let a = 1 ; }"), "fn f ( ) {\n    let a = 1 ;\n}\n");
+    }
+
+    #[test]
+    fn test_pretty_print_literals() {
+        assert_eq!(pretty_print("let a = \"}\" ;"), "let a = \"}\" ;\n");
+        assert_eq!(pretty_print("let a = '}' ;"), "let a = '}' ;\n");
+        assert_eq!(pretty_print("let a = '\\'' ;"), "let a = '\\'' ;\n");
+        assert_eq!(pretty_print("let a = '\\u{7d}' ;"), "let a = '\\u{7d}' ;\n");
+        assert_eq!(pretty_print("fn f < 'a > ( a : & 'a str ) { }"), "fn f < 'a > ( a : & 'a str ) {\n}\n");
+    }
+
+    #[test]
+    fn test_pretty_print_unbalanced() {
+        assert_eq!(pretty_print("} }"), "}\n}\n");
+    }
+}
@@ -0,0 +1,198 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::{Ident, ToTokens, Tokens};
+
+use assemble::GUARD;
+use css;
+use template::{Attribute, AttributeValue, Element, CFG, CFG_MARKER};
+
+// Custom `x-` elements are declared with `define_element!`, which expands to a
+// marker type named after the element. Each declared attribute becomes an
+// associated identity function over the attribute's type, and `style()`
+// returns the element's default style declarations, if it has some.
+//
+// `rsx!` then passes every attribute value through its function where it's
+// used, so that rustc reports unknown elements, unknown attributes and
+// mismatched value types at compile time, and adds the default style to
+// elements without a `style` attribute when `style()` returns one.
+
+pub const PREFIX: &str = "x-";
+
+const DEFAULT_SELECTOR: &str = ".__rsx_default";
+
+pub struct Definition {
+    pub name: String,
+    pub attributes: Vec<(String, String)>,
+    pub style: String
+}
+
+pub fn type_name(element: &str) -> Ident {
+    let mut name = "__RsxElement".to_string();
+    for part in element.split(|c: char| !c.is_alphanumeric()) {
+        let mut chars = part.chars();
+        if let Some(first) = chars.next() {
+            name.extend(first.to_uppercase());
+            name.push_str(chars.as_str());
+        }
+    }
+    Ident::new(name)
+}
+
+// Parses `x-name { attribute: Type, ... } style { declarations }`.
+pub fn parse_definition(source: &str) -> Definition {
+    let open = source.find('{').expect("Expected `{ attribute: Type, ... }` after the element name");
+    let name = source[..open].split_whitespace().collect::<String>();
+    if !name.starts_with(PREFIX) {
+        panic!("Custom element names should start with `{}`, found `{}`", PREFIX, name);
+    }
+
+    let close = css::matching_brace(source, open).unwrap_or_else(|| panic!("Unbalanced braces in `{}`", source));
+    let attributes = split_fields(&source[open + 1..close])
+        .into_iter()
+        .map(|field| {
+            let colon = field.find(':').expect(&format!("Expected `attribute: Type` but found `{}`", field));
+            let attribute = field[..colon].split_whitespace().collect::<String>();
+            (attribute, field[colon + 1..].trim().to_string())
+        })
+        .collect();
+
+    let rest = source[close + 1..].trim();
+    let style = if rest.is_empty() {
+        String::new()
+    } else {
+        if !rest.starts_with("style") {
+            panic!("Expected a `style {{ ... }}` block but found `{}`", rest);
+        }
+        let open = rest.find('{').expect("Expected `{` after `style`");
+        let close = css::matching_brace(rest, open).unwrap_or_else(|| panic!("Unbalanced braces in `{}`", rest));
+        rest[open + 1..close].to_string()
+    };
+
+    Definition {
+        name,
+        attributes,
+        style
+    }
+}
+
+pub fn default_style_source(definition: &Definition) -> String {
+    format!("{} {{ {} }}", DEFAULT_SELECTOR, definition.style)
+}
+
+pub fn expand_definition<T: ToTokens>(definition: &Definition, stylesheet: &T, types: &str) -> Tokens {
+    let type_name = type_name(&definition.name);
+    let types_path = Ident::new(types);
+    let attributes = definition
+        .attributes
+        .iter()
+        .map(|&(ref name, _)| Ident::new(attribute_fn(name)));
+    let types = definition
+        .attributes
+        .iter()
+        .map(|&(_, ref ty)| Ident::new(ty.as_str()))
+        .collect::<Vec<_>>();
+    let return_types = types.clone();
+    let style = if definition.style.trim().is_empty() {
+        quote! { None }
+    } else {
+        quote! {
+            let mut stylesheet = #stylesheet;
+            Some(stylesheet.take(#DEFAULT_SELECTOR))
+        }
+    };
+
+    quote! {
+        #[allow(dead_code)]
+        struct #type_name;
+
+        #[allow(dead_code)]
+        impl #type_name {
+            #(fn #attributes(value: #types) -> #return_types { value })*
+
+            fn style() -> Option<#types_path::StyleDeclarations> {
+                #style
+            }
+        }
+    }
+}
+
+// Passes the attribute values of a custom element through the functions of
+// its definition, and gives it its default style when it isn't given one.
+pub fn lower(mut element: Element) -> Element {
+    if !element.name.starts_with(PREFIX) {
+        return element;
+    }
+
+    let type_name = type_name(&element.name);
+    let mut has_style = false;
+
+    for attribute in element.attributes.iter_mut() {
+        let (name, value) = match *attribute {
+            Attribute::Named(ref name, ref mut value) => (name, value),
+            Attribute::Spread(_) => panic!("Spread attributes aren't supported on custom elements")
+        };
+        if name == CFG || name == CFG_MARKER || name == GUARD {
+            continue;
+        }
+
+        let code = match value.take() {
+            Some(AttributeValue::Str(code)) | Some(AttributeValue::Literal(code)) | Some(AttributeValue::Block(code)) => code,
+            None => "true".to_string()
+        };
+        let checked = if name == "style" {
+            has_style = true;
+            format!("let _ = {}::style; {}", type_name, code)
+        } else {
+            format!("{}::{}({{ {} }})", type_name, attribute_fn(name), code)
+        };
+        *value = Some(AttributeValue::Block(checked));
+    }
+
+    if !has_style {
+        let guard = format!("if let Some(__rsx_style) = {}::style()", type_name);
+        element.attributes.push(Attribute::Named(GUARD.to_string(), Some(AttributeValue::Block(guard))));
+        element.attributes.push(Attribute::Named("style".to_string(), Some(AttributeValue::Block("__rsx_style".to_string()))));
+    }
+    element
+}
+
+fn attribute_fn(name: &str) -> String {
+    name.split_whitespace().collect::<String>().replace("-", "_")
+}
+
+// Splits on commas outside of any brackets, including generic arguments.
+pub fn split_fields(source: &str) -> Vec<String> {
+    let mut fields = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+
+    for c in source.chars() {
+        match c {
+            '(' | '[' | '{' | '<' => depth += 1,
+            ')' | ']' | '}' | '>' => depth -= 1,
+            ',' if depth == 0 => {
+                fields.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+
+    if !current.trim().is_empty() {
+        fields.push(current.trim().to_string());
+    }
+
+    fields.retain(|field| !field.is_empty());
+    fields
+}
@@ -0,0 +1,303 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::{Ident, Tokens};
+use regex::Regex;
+
+use template::{unquote, Attribute, AttributeValue, Element, Node};
+
+const VOID_ELEMENTS: &[&str] = &[
+    "area", "base", "br", "col", "embed", "hr", "img", "input", "link", "meta", "param", "source", "track", "wbr"
+];
+
+pub const STYLE_ATTRIBUTE: &str = "style";
+
+// `style` attributes given as code blocks are written through this trait, so
+// that they can be strings of CSS or the `StyleDeclarations` of a `css!`
+// stylesheet. Declarations without a CSS counterpart are left out. As in
+// composed style lists, the stylesheet types are qualified once expanded.
+const STYLE_TEXT: &str = r#"
+    trait __RsxStyleText {
+        fn style_text(&self) -> String;
+    }
+    impl<'a> __RsxStyleText for &'a str {
+        fn style_text(&self) -> String {
+            self.to_string()
+        }
+    }
+    impl __RsxStyleText for String {
+        fn style_text(&self) -> String {
+            self.clone()
+        }
+    }
+    impl __RsxStyleText for __rsx_stylesheet_types::StyleDeclarations {
+        #[allow(unreachable_patterns)]
+        fn style_text(&self) -> String {
+            use __rsx_stylesheet_types::*;
+            let mut text = vec![];
+            for declaration in self.0.iter() {
+                let (name, value) = match *declaration {
+                    StyleDeclaration::Layout(ref style) => match *style {
+                        FlexStyle::Width(ref unit) => ("width", __rsx_unit(unit)),
+                        FlexStyle::Height(ref unit) => ("height", __rsx_unit(unit)),
+                        FlexStyle::MinWidth(ref unit) => ("min-width", __rsx_unit(unit)),
+                        FlexStyle::MinHeight(ref unit) => ("min-height", __rsx_unit(unit)),
+                        FlexStyle::MaxWidth(ref unit) => ("max-width", __rsx_unit(unit)),
+                        FlexStyle::MaxHeight(ref unit) => ("max-height", __rsx_unit(unit)),
+                        FlexStyle::Top(ref unit) => ("top", __rsx_unit(unit)),
+                        FlexStyle::Right(ref unit) => ("right", __rsx_unit(unit)),
+                        FlexStyle::Bottom(ref unit) => ("bottom", __rsx_unit(unit)),
+                        FlexStyle::Left(ref unit) => ("left", __rsx_unit(unit)),
+                        FlexStyle::MarginTop(ref unit) => ("margin-top", __rsx_unit(unit)),
+                        FlexStyle::MarginRight(ref unit) => ("margin-right", __rsx_unit(unit)),
+                        FlexStyle::MarginBottom(ref unit) => ("margin-bottom", __rsx_unit(unit)),
+                        FlexStyle::MarginLeft(ref unit) => ("margin-left", __rsx_unit(unit)),
+                        FlexStyle::PaddingTop(ref unit) => ("padding-top", __rsx_unit(unit)),
+                        FlexStyle::PaddingRight(ref unit) => ("padding-right", __rsx_unit(unit)),
+                        FlexStyle::PaddingBottom(ref unit) => ("padding-bottom", __rsx_unit(unit)),
+                        FlexStyle::PaddingLeft(ref unit) => ("padding-left", __rsx_unit(unit)),
+                        FlexStyle::FlexBasis(ref unit) => ("flex-basis", __rsx_unit(unit)),
+                        FlexStyle::FlexGrow(ref value) => ("flex-grow", Some(value.to_string())),
+                        FlexStyle::FlexShrink(ref value) => ("flex-shrink", Some(value.to_string())),
+                        FlexStyle::FlexDirection(ref value) => ("flex-direction", Some(__rsx_keyword(value))),
+                        FlexStyle::FlexWrap(ref value) => ("flex-wrap", Some(__rsx_keyword(value))),
+                        FlexStyle::AlignContent(ref value) => ("align-content", Some(__rsx_keyword(value))),
+                        FlexStyle::AlignItems(ref value) => ("align-items", Some(__rsx_keyword(value))),
+                        FlexStyle::AlignSelf(ref value) => ("align-self", Some(__rsx_keyword(value))),
+                        FlexStyle::JustifyContent(ref value) => ("justify-content", Some(__rsx_keyword(value))),
+                        _ => continue
+                    },
+                    StyleDeclaration::Theme(ref style) => match *style {
+                        ThemeStyle::BackgroundColor(ref color) => ("background-color", Some(__rsx_color(color))),
+                        ThemeStyle::Color(ref color) => ("color", Some(__rsx_color(color))),
+                        ThemeStyle::Opacity(opacity) => ("opacity", Some((opacity as f32 / 100.0).to_string())),
+                        _ => continue
+                    },
+                    _ => continue
+                };
+                if let Some(value) = value {
+                    text.push(format!("{}: {}", name, value));
+                }
+            }
+            text.join("; ")
+        }
+    }
+    #[allow(unreachable_patterns)]
+    fn __rsx_unit(unit: &__rsx_stylesheet_types::StyleUnit) -> Option<String> {
+        match *unit {
+            __rsx_stylesheet_types::StyleUnit::Point(ref value) => Some(format!("{}px", value)),
+            __rsx_stylesheet_types::StyleUnit::Percent(ref value) => Some(format!("{}%", value)),
+            __rsx_stylesheet_types::StyleUnit::Auto => Some("auto".to_string()),
+            _ => None
+        }
+    }
+    fn __rsx_color(color: &__rsx_stylesheet_types::Color) -> String {
+        format!("rgba({}, {}, {}, {})", color.red, color.green, color.blue, color.alpha as f32 / 255.0)
+    }
+    // Keywords are the names of the variants in kebab case, as in `row-reverse`.
+    fn __rsx_keyword<T: ::std::fmt::Debug>(value: &T) -> String {
+        let mut keyword = String::new();
+        for c in format!("{:?}", value).chars() {
+            if c.is_uppercase() && !keyword.is_empty() {
+                keyword.push('-');
+            }
+            keyword.extend(c.to_lowercase());
+        }
+        match keyword.as_str() {
+            "no-wrap" => "nowrap".to_string(),
+            _ => keyword
+        }
+    }
+"#;
+
+enum Segment {
+    Static(String),
+    Escaped(String),
+    Style(String)
+}
+
+pub struct Writer {
+    segments: Vec<Segment>,
+    markers: Option<usize>
+}
+
+impl Writer {
+    pub fn new() -> Self {
+        Writer {
+            segments: vec![],
+            markers: None
+        }
+    }
+
+    // Wraps every code block child in `<!--rsx:N-->` comments, numbered in
+    // document order, so that `rsx_hydrate!` can find their output again.
+    pub fn with_markers() -> Self {
+        Writer {
+            segments: vec![],
+            markers: Some(0)
+        }
+    }
+
+    pub fn write_nodes(&mut self, nodes: &[Node]) {
+        for node in nodes {
+            self.write_node(node);
+        }
+    }
+
+    // Builds a block expression evaluating to the rendered `String`. Static
+    // markup is concatenated here, only code blocks are written at runtime.
+    pub fn expand(&self) -> Tokens {
+        let capacity = self.segments
+            .iter()
+            .map(|segment| match *segment {
+                Segment::Static(ref html) => html.len(),
+                _ => 0
+            })
+            .sum::<usize>();
+
+        let writes = self.segments.iter().map(|segment| match *segment {
+            Segment::Static(ref html) => quote! {
+                __rsx_html.push_str(#html);
+            },
+            Segment::Escaped(ref code) => {
+                let code = Ident::new(code.as_str());
+                quote! {
+                    __rsx_escape(&mut __rsx_html, &::std::string::ToString::to_string(&(#code)));
+                }
+            }
+            Segment::Style(ref code) => {
+                let code = Ident::new(code.as_str());
+                quote! {
+                    __rsx_escape(&mut __rsx_html, &(#code).style_text());
+                }
+            }
+        });
+
+        let has_styles = self.segments.iter().any(|segment| match *segment {
+            Segment::Style(_) => true,
+            _ => false
+        });
+        let style_text = if has_styles {
+            Ident::new(STYLE_TEXT)
+        } else {
+            Ident::new("")
+        };
+
+        quote! {
+            {
+                #[allow(dead_code)]
+                fn __rsx_escape(html: &mut String, value: &str) {
+                    html.push_str(&value
+                        .replace('&', "&amp;")
+                        .replace('<', "&lt;")
+                        .replace('>', "&gt;")
+                        .replace('"', "&quot;")
+                        .replace('\'', "&#39;"));
+                }
+
+                #style_text
+
+                let mut __rsx_html = String::with_capacity(#capacity);
+                #(#writes)*
+                __rsx_html
+            }
+        }
+    }
+
+    fn push_static(&mut self, html: &str) {
+        if let Some(&mut Segment::Static(ref mut last)) = self.segments.last_mut() {
+            last.push_str(html);
+            return;
+        }
+        self.segments.push(Segment::Static(html.to_string()));
+    }
+
+    fn write_node(&mut self, node: &Node) {
+        match *node {
+            Node::Text(ref text) => {
+                let escaped = escape(text);
+                self.push_static(&escaped);
+            }
+            Node::Block(ref code) => {
+                // Nested `rsx_html!` invocations are inlined, so that they're
+                // neither rendered separately nor escaped twice.
+                if let Some(source) = nested_source(code) {
+                    self.write_nodes(&::parse_template(&source));
+                    return;
+                }
+                let marker = self.markers.as_mut().map(|next| {
+                    *next += 1;
+                    *next - 1
+                });
+                if let Some(id) = marker {
+                    self.push_static(&format!("<!--rsx:{}-->", id));
+                }
+                self.segments.push(Segment::Escaped(code.clone()));
+                if let Some(id) = marker {
+                    self.push_static(&format!("<!--/rsx:{}-->", id));
+                }
+            }
+            Node::Element(ref element) => self.write_element(element)
+        }
+    }
+
+    fn write_element(&mut self, element: &Element) {
+        self.push_static(&format!("<{}", element.name));
+
+        for attribute in &element.attributes {
+            match *attribute {
+                Attribute::Named(ref name, None) => self.push_static(&format!(" {}", name)),
+                Attribute::Named(ref name, Some(AttributeValue::Str(ref value))) => {
+                    let value = escape(&unquote(value).unwrap());
+                    self.push_static(&format!(" {}=\"{}\"", name, value));
+                }
+                Attribute::Named(ref name, Some(AttributeValue::Literal(ref value))) => {
+                    self.push_static(&format!(" {}=\"{}\"", name, escape(value)));
+                }
+                Attribute::Named(ref name, Some(AttributeValue::Block(ref code))) => {
+                    self.push_static(&format!(" {}=\"", name));
+                    if name == STYLE_ATTRIBUTE {
+                        self.segments.push(Segment::Style(code.clone()));
+                    } else {
+                        self.segments.push(Segment::Escaped(code.clone()));
+                    }
+                    self.push_static("\"");
+                }
+                Attribute::Spread(_) => panic!("Spread attributes aren't supported by `rsx_html!`")
+            }
+        }
+
+        if element.self_closing && VOID_ELEMENTS.contains(&element.name.as_str()) {
+            self.push_static(">");
+            return;
+        }
+
+        self.push_static(">");
+        self.write_nodes(&element.children);
+        self.push_static(&format!("</{}>", element.name));
+    }
+}
+
+// Returns the template given to a `rsx_html!` invocation inside a code block.
+pub fn nested_source(code: &str) -> Option<String> {
+    let re_nested = Regex::new(r"(?s)^rsx_html\s*!\s*\{(?P<source>.*)\}$").unwrap();
+    re_nested
+        .captures(code)
+        .map(|captures| captures["source"].to_string())
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
@@ -0,0 +1,206 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::{Ident, ToTokens, Tokens};
+use regex::Regex;
+
+use css::matching_brace;
+use html::nested_source;
+use template::{split_top_level, unquote, Attribute, AttributeValue, Element, Node};
+
+pub const ID_ATTRIBUTE: &str = "data-rsx-id";
+
+// Both `rsx_html!(hydrate, ...)` and `rsx_hydrate!` run this pass first, so
+// that elements get the same ids on the server and on the client. Elements
+// with dynamic attributes or children are tagged with a `data-rsx-id`.
+pub fn mark(nodes: &mut Vec<Node>) {
+    inline_nested(nodes);
+    let mut next = 0;
+    mark_elements(nodes, &mut next);
+}
+
+// Replaces each code block child with the text it rendered to, as found
+// between the `<!--rsx:N-->` markers of the server side HTML, and each code
+// block attribute with its value on the element with the same `data-rsx-id`.
+// Nothing is evaluated again, so the client doesn't need any of the values
+// the server rendered with.
+pub fn restore(nodes: &mut Vec<Node>) {
+    let mut next = 0;
+    restore_blocks(nodes, &mut next);
+}
+
+// A `#[component(hydrate)]` function rendering with `rsx_html!(hydrate, ...)`
+// also defines a `__rsx_hydrate_<name>!` macro holding its template, so that
+// `rsx_hydrate!(html, name)` can rebuild the tree without the template being
+// copied. Being a `macro_rules!`, it's only visible after the function, in the
+// same module or with `#[macro_use]`.
+pub fn expand_component(source: &str) -> Tokens {
+    let re_fn = Regex::new(r"(?:^|\s)fn\s+(?P<name>[a-zA-Z_][a-zA-Z0-9_]*)").unwrap();
+    let re_html = Regex::new(r"rsx_html\s*!\s*[(\[{]\s*hydrate\s*,").unwrap();
+
+    let name = &re_fn.captures(source).expect("Components should be functions")["name"];
+    let found = re_html
+        .find(source)
+        .expect("Hydrated components should render with `rsx_html!(hydrate, ...)`");
+    let open = source[..found.end()].rfind(|c: char| c == '(' || c == '[' || c == '{').unwrap();
+    let close = matching_brace(source, open).expect("Unbalanced brackets in hydrated component");
+    let template = &source[found.end()..close];
+
+    let mut tokens = Tokens::new();
+    tokens.append(format!(
+        "#[allow(unused_macros)] macro_rules! {} {{ ($($args:tt)*) => {{ rsx_hydrate!($($args)*, {}) }} }}",
+        macro_name(name),
+        template
+    ));
+    tokens
+}
+
+pub fn macro_name(component: &str) -> String {
+    format!("__rsx_hydrate_{}", component)
+}
+
+// Splits `rsx_hydrate!` arguments into the HTML expression and the template,
+// which is kept as it's written.
+pub fn split_args(source: &str) -> (String, String) {
+    let parts = split_top_level(source);
+    if parts.len() < 2 {
+        panic!("Expected `rsx_hydrate!(html, <template>)`");
+    }
+    let html = parts[0].clone();
+    let rest = &source[source.find(html.as_str()).unwrap() + html.len()..];
+    let template = rest[rest.find(',').unwrap() + 1..].to_string();
+    (html, template)
+}
+
+pub fn expand<T: ToTokens>(html: &str, ast: &T) -> Tokens {
+    let html = Ident::new(html);
+
+    quote! {
+        {
+            #[allow(dead_code)]
+            fn __rsx_unescape(html: &str) -> String {
+                html.replace("&lt;", "<")
+                    .replace("&gt;", ">")
+                    .replace("&quot;", "\"")
+                    .replace("&#39;", "'")
+                    .replace("&amp;", "&")
+            }
+
+            #[allow(dead_code)]
+            fn __rsx_between<'a>(html: &'a str, start: &str, end: &str) -> &'a str {
+                let from = html.find(start).expect("Missing hydration marker") + start.len();
+                let to = html[from..].find(end).expect("Missing hydration marker") + from;
+                &html[from..to]
+            }
+
+            #[allow(dead_code)]
+            fn __rsx_hydrated(html: &str, id: usize) -> String {
+                __rsx_unescape(__rsx_between(html, &format!("<!--rsx:{}-->", id), &format!("<!--/rsx:{}-->", id)))
+            }
+
+            #[allow(dead_code)]
+            fn __rsx_hydrated_attribute(html: &str, id: &str, name: &str) -> String {
+                let marker = format!(" data-rsx-id=\"{}\"", id);
+                let at = html.find(&marker).expect("Missing hydration marker");
+                let tag = &html[html[..at].rfind('<').expect("Missing hydration marker")..at];
+                __rsx_unescape(__rsx_between(tag, &format!(" {}=\"", name), "\""))
+            }
+
+            let __rsx_html: &str = ::std::convert::AsRef::as_ref(&#html);
+
+            fragment! {
+                #ast
+            }
+        }
+    }
+}
+
+fn inline_nested(nodes: &mut Vec<Node>) {
+    let mut inlined = vec![];
+
+    for node in nodes.drain(..) {
+        match node {
+            Node::Block(code) => match nested_source(&code) {
+                Some(source) => {
+                    let mut nested = ::parse_template(&source);
+                    inline_nested(&mut nested);
+                    inlined.extend(nested);
+                }
+                None => inlined.push(Node::Block(code))
+            },
+            Node::Element(mut element) => {
+                inline_nested(&mut element.children);
+                inlined.push(Node::Element(element));
+            }
+            node => inlined.push(node)
+        }
+    }
+
+    *nodes = inlined;
+}
+
+fn mark_elements(nodes: &mut Vec<Node>, next: &mut usize) {
+    for node in nodes.iter_mut() {
+        if let Node::Element(ref mut element) = *node {
+            let has_dynamic_attributes = element.attributes.iter().any(|attribute| match *attribute {
+                Attribute::Named(_, Some(AttributeValue::Block(_))) | Attribute::Spread(_) => true,
+                _ => false
+            });
+            let has_dynamic_children = element.children.iter().any(|child| match *child {
+                Node::Block(_) => true,
+                _ => false
+            });
+
+            if has_dynamic_attributes || has_dynamic_children {
+                let id = format!("\"{}\"", next);
+                element.attributes.push(Attribute::Named(ID_ATTRIBUTE.to_string(), Some(AttributeValue::Str(id))));
+                *next += 1;
+            }
+
+            mark_elements(&mut element.children, next);
+        }
+    }
+}
+
+fn restore_blocks(nodes: &mut Vec<Node>, next: &mut usize) {
+    for node in nodes.iter_mut() {
+        let restored = match *node {
+            Node::Block(_) => {
+                let id = *next;
+                *next += 1;
+                Some(Node::Block(format!("__rsx_hydrated(__rsx_html, {})", id)))
+            }
+            Node::Element(ref mut element) => {
+                restore_attributes(element);
+                restore_blocks(&mut element.children, next);
+                None
+            }
+            Node::Text(_) => None
+        };
+        if let Some(restored) = restored {
+            *node = restored;
+        }
+    }
+}
+
+fn restore_attributes(element: &mut Element) {
+    let id = match element.attribute(ID_ATTRIBUTE) {
+        Some(&AttributeValue::Str(ref id)) => unquote(id).unwrap(),
+        _ => return
+    };
+
+    for attribute in element.attributes.iter_mut() {
+        if let Attribute::Named(ref name, ref mut value @ Some(AttributeValue::Block(_))) = *attribute {
+            let code = format!("__rsx_hydrated_attribute(__rsx_html, {:?}, {:?})", id, name);
+            *value = Some(AttributeValue::Block(code));
+        }
+    }
+}
@@ -0,0 +1,335 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use quote::{Ident, Tokens};
+use regex::Regex;
+
+use template::{split_top_level, unquote, Attribute, AttributeValue, Element, Node};
+
+// Message catalogs are Fluent (.ftl) files, either as `locales/<locale>.ftl` or
+// as any number of `locales/<locale>/*.ftl` files. Only plain messages with
+// `{ $variable }` and `{ "literal" }` placeables are supported. Unless given a
+// locale, lookups use the one set through the `rsx_context` module.
+
+type Catalog = BTreeMap<String, Vec<Piece>>;
+
+#[derive(Debug, Clone, PartialEq)]
+enum Piece {
+    Text(String),
+    Variable(String)
+}
+
+pub struct Message {
+    pub id: String,
+    pub locale: Option<String>,
+    pub args: Vec<(String, String)>
+}
+
+// Replaces `<t id="..." arg={...} />` elements with the runtime lookup.
+pub fn translate_nodes(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        let replacement = match *node {
+            Node::Element(ref mut element) => {
+                if element.name == "t" {
+                    Some(Node::Block(expand(&message_from_element(element)).into_string()))
+                } else {
+                    translate_nodes(&mut element.children);
+                    None
+                }
+            }
+            _ => None
+        };
+        if let Some(replacement) = replacement {
+            *node = replacement;
+        }
+    }
+}
+
+// Parses the arguments given to `t!`, as in `"greeting", name = n`.
+pub fn message_from_args(source: &str) -> Message {
+    let mut parts = split_top_level(source).into_iter();
+    let id = parts.next().expect("Expected a message id");
+    let mut message = Message {
+        id: unquote(&id).expect("Message id should be a string literal"),
+        locale: None,
+        args: vec![]
+    };
+
+    for part in parts {
+        let eq = part.find('=').expect(&format!("Expected `name = value` but found `{}`", part));
+        let name = part[..eq].trim().to_string();
+        let value = part[eq + 1..].trim().to_string();
+        if name == "locale" {
+            message.locale = Some(value);
+        } else {
+            message.args.push((name, value));
+        }
+    }
+
+    message
+}
+
+fn message_from_element(element: &Element) -> Message {
+    if !element.self_closing {
+        panic!("Translated `<t>` elements can't have children");
+    }
+
+    let mut message = Message {
+        id: match element.attribute("id") {
+            Some(&AttributeValue::Str(ref id)) => unquote(id).unwrap(),
+            _ => panic!("Translated `<t>` elements need a string `id` attribute")
+        },
+        locale: None,
+        args: vec![]
+    };
+
+    for attribute in &element.attributes {
+        let (name, value) = match *attribute {
+            Attribute::Named(ref name, Some(AttributeValue::Block(ref value))) |
+            Attribute::Named(ref name, Some(AttributeValue::Str(ref value))) |
+            Attribute::Named(ref name, Some(AttributeValue::Literal(ref value))) => (name, value),
+            _ => panic!("Unsupported attribute on translated `<t>` element")
+        };
+        match name.as_str() {
+            "id" => {}
+            "locale" => message.locale = Some(value.clone()),
+            _ => message.args.push((name.clone(), value.clone()))
+        }
+    }
+
+    message
+}
+
+pub fn expand(message: &Message) -> Tokens {
+    let mut files = vec![];
+    let catalogs = load_catalogs(&mut files);
+    if catalogs.is_empty() {
+        panic!("No message catalogs found in {}", locales_dir().to_string_lossy());
+    }
+
+    let provided = message.args.iter().map(|&(ref name, _)| name.as_str()).collect::<BTreeSet<_>>();
+    let mut referenced = BTreeSet::new();
+
+    for (locale, catalog) in &catalogs {
+        let pieces = catalog
+            .get(&message.id)
+            .expect(&format!("Missing message `{}` in locale `{}`", message.id, locale));
+
+        for piece in pieces {
+            if let Piece::Variable(ref name) = *piece {
+                if !provided.contains(name.as_str()) {
+                    panic!("Message `{}` in locale `{}` needs a `{}` argument", message.id, locale, name);
+                }
+                referenced.insert(name.as_str());
+            }
+        }
+    }
+
+    for name in &provided {
+        if !referenced.contains(name) {
+            panic!("Message `{}` doesn't take a `{}` argument in any locale", message.id, name);
+        }
+    }
+
+    let bindings = message.args.iter().map(|&(ref name, _)| binding(name)).collect::<Vec<_>>();
+    let values = message.args.iter().map(|&(ref _name, ref value)| Ident::new(value.as_str()));
+
+    let locales = catalogs.keys().collect::<Vec<_>>();
+    let formatted = catalogs.values().map(|catalog| format_pieces(&catalog[&message.id])).collect::<Vec<_>>();
+    let default_locale = env::var("RSX_DEFAULT_LOCALE").unwrap_or_else(|_| locales[0].clone());
+    let default = format_pieces(
+        &catalogs
+            .get(&default_locale)
+            .expect(&format!("Missing default locale `{}`", default_locale))[&message.id]
+    );
+
+    let locale = match message.locale {
+        Some(ref locale) => Ident::new(locale.as_str()),
+        None => Ident::new("::rsx_context::locale().unwrap_or_default()")
+    };
+
+    let dependencies = ::paths::expand_dependencies(&files);
+
+    let locales_1 = &locales;
+    let locales_2 = &locales;
+    let formatted_1 = &formatted;
+    let formatted_2 = &formatted;
+
+    quote! {
+        {
+            #dependencies
+            #(let #bindings = &(#values);)*
+            let locale = ::std::string::ToString::to_string(&#locale);
+            match locale.as_str() {
+                #(#locales_1 => #formatted_1,)*
+                _ => match locale.split(|c: char| c == '-' || c == '_').next().unwrap_or("") {
+                    #(#locales_2 => #formatted_2,)*
+                    _ => #default
+                }
+            }
+        }
+    }
+}
+
+fn binding(name: &str) -> Ident {
+    Ident::new(format!("__rsx_arg_{}", name.replace("-", "_")))
+}
+
+fn format_pieces(pieces: &[Piece]) -> Tokens {
+    let mut template = String::new();
+    let mut args = vec![];
+
+    for piece in pieces {
+        match *piece {
+            Piece::Text(ref text) => template.push_str(&text.replace("{", "{{").replace("}", "}}")),
+            Piece::Variable(ref name) => {
+                template.push_str("{}");
+                args.push(binding(name));
+            }
+        }
+    }
+
+    quote! {
+        format!(#template, #(#args),*)
+    }
+}
+
+// The directory can be set with `locales = "path"` under
+// `[package.metadata.rsx]` in the crate's manifest.
+fn locales_dir() -> PathBuf {
+    let dir = env::var("RSX_LOCALES_DIR")
+        .ok()
+        .or_else(manifest_locales_dir)
+        .unwrap_or_else(|| "locales".to_string());
+    ::paths::resolve(&dir)
+}
+
+fn manifest_locales_dir() -> Option<String> {
+    let manifest = PathBuf::from(env::var("CARGO_MANIFEST_DIR").ok()?).join("Cargo.toml");
+    let manifest = ::css::read_file(&manifest);
+    let re_locales = Regex::new(r#"^locales\s*=\s*"(?P<dir>[^"]*)"$"#).unwrap();
+
+    let mut in_table = false;
+    for line in manifest.lines() {
+        let line = line.trim();
+        if line.starts_with('[') {
+            in_table = line == "[package.metadata.rsx]";
+        } else if in_table {
+            if let Some(captures) = re_locales.captures(line) {
+                return Some(captures["dir"].to_string());
+            }
+        }
+    }
+    None
+}
+
+// Also returns the files the catalogs were read from.
+fn load_catalogs(files: &mut Vec<PathBuf>) -> BTreeMap<String, Catalog> {
+    let mut catalogs = BTreeMap::new();
+    let entries = match fs::read_dir(locales_dir()) {
+        Ok(entries) => entries,
+        Err(_) => return catalogs
+    };
+
+    for entry in entries {
+        let path = entry.unwrap().path();
+        let locale = path.file_stem().unwrap().to_string_lossy().into_owned();
+        let mut catalog = Catalog::new();
+
+        if path.is_dir() {
+            for file in fs::read_dir(&path).unwrap() {
+                let file = file.unwrap().path();
+                if file.extension().map_or(false, |ext| ext == "ftl") {
+                    parse_catalog(&file, &mut catalog);
+                    files.push(file);
+                }
+            }
+        } else if path.extension().map_or(false, |ext| ext == "ftl") {
+            parse_catalog(&path, &mut catalog);
+            files.push(path.clone());
+        } else {
+            continue;
+        }
+
+        catalogs.entry(locale).or_insert_with(Catalog::new).extend(catalog);
+    }
+
+    catalogs
+}
+
+fn parse_catalog(file_path: &Path, catalog: &mut Catalog) {
+    let file_contents = ::css::read_file(file_path);
+
+    // Pending message as (id, value, location).
+    let mut current: Option<(String, String, String)> = None;
+
+    for (index, line) in file_contents.lines().enumerate() {
+        if line.starts_with(' ') && current.is_some() {
+            // Indented lines continue the previous message.
+            if let Some((_, ref mut value, _)) = current {
+                value.push('\n');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+
+        if let Some((id, value, location)) = current.take() {
+            catalog.insert(id, parse_pattern(&value, &location));
+        }
+
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let location = format!("{}:{}", file_path.to_string_lossy(), index + 1);
+        let eq = line.find('=').expect(&format!("Expected `id = message` at {}", location));
+        current = Some((line[..eq].trim().to_string(), line[eq + 1..].trim().to_string(), location));
+    }
+
+    if let Some((id, value, location)) = current.take() {
+        catalog.insert(id, parse_pattern(&value, &location));
+    }
+}
+
+fn parse_pattern(value: &str, location: &str) -> Vec<Piece> {
+    let mut pieces = vec![];
+    let mut rest = value;
+
+    while let Some(start) = rest.find('{') {
+        if start > 0 {
+            pieces.push(Piece::Text(rest[..start].to_string()));
+        }
+        let end = rest[start..].find('}').expect(&format!("Unclosed placeable at {}", location)) + start;
+        let placeable = rest[start + 1..end].trim();
+
+        if placeable.starts_with('$') {
+            pieces.push(Piece::Variable(placeable[1..].to_string()));
+        } else if let Some(literal) = unquote(placeable) {
+            pieces.push(Piece::Text(literal));
+        } else {
+            panic!("Unsupported Fluent placeable `{}` at {}", placeable, location);
+        }
+
+        rest = &rest[end + 1..];
+    }
+
+    if !rest.is_empty() {
+        pieces.push(Piece::Text(rest.to_string()));
+    }
+
+    pieces
+}
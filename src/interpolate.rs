@@ -0,0 +1,112 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::Ident;
+use regex::Regex;
+
+use template::Node;
+
+struct Capture {
+    path: String,
+    spec: Option<String>
+}
+
+// Turns runs of text and `{name}` or `{name:spec}` captures into a single
+// `format!` text node. A run is only rewritten when it actually interpolates
+// into some text, or uses a format spec, so that lone `{ value }` children such
+// as nested `DOMTree`s keep being inserted as they are.
+pub fn format_text_nodes(nodes: &mut Vec<Node>) {
+    let re_capture = Regex::new(
+        r"^(?P<path>[a-zA-Z_][a-zA-Z0-9_]*(?:\s*\.\s*[a-zA-Z_][a-zA-Z0-9_]*)*)\s*(?::(?P<spec>[^:].*))?$"
+    ).unwrap();
+
+    format_nodes(&re_capture, nodes);
+}
+
+fn format_nodes(re_capture: &Regex, nodes: &mut Vec<Node>) {
+    let mut formatted = vec![];
+    let mut run = vec![];
+
+    for node in nodes.drain(..) {
+        match node {
+            Node::Text(_) => run.push(node),
+            Node::Block(code) => {
+                if re_capture.is_match(&code) {
+                    run.push(Node::Block(code));
+                } else {
+                    flush_run(re_capture, &mut run, &mut formatted);
+                    formatted.push(Node::Block(code));
+                }
+            }
+            Node::Element(mut element) => {
+                flush_run(re_capture, &mut run, &mut formatted);
+                format_nodes(re_capture, &mut element.children);
+                formatted.push(Node::Element(element));
+            }
+        }
+    }
+
+    flush_run(re_capture, &mut run, &mut formatted);
+    *nodes = formatted;
+}
+
+fn flush_run(re_capture: &Regex, run: &mut Vec<Node>, formatted: &mut Vec<Node>) {
+    let has_text = run.iter().any(|node| match *node {
+        Node::Text(_) => true,
+        _ => false
+    });
+
+    let mut template = String::new();
+    let mut captures = vec![];
+
+    for node in run.iter() {
+        if !template.is_empty() {
+            template.push(' ');
+        }
+        match *node {
+            Node::Text(ref text) => template.push_str(&text.replace("{", "{{").replace("}", "}}")),
+            Node::Block(ref code) => {
+                let capture = parse_capture(re_capture, code);
+                match capture.spec {
+                    Some(ref spec) => template.push_str(&format!("{{:{}}}", spec)),
+                    None => template.push_str("{}")
+                }
+                captures.push(capture);
+            }
+            Node::Element(_) => unreachable!()
+        }
+    }
+
+    let has_spec = captures.iter().any(|capture| capture.spec.is_some());
+
+    if captures.is_empty() || !(has_text || has_spec) {
+        formatted.extend(run.drain(..));
+        return;
+    }
+
+    let args = captures.iter().map(|capture| Ident::new(capture.path.as_str()));
+    let expanded = quote! {
+        format!(#template, #(#args),*)
+    };
+
+    run.clear();
+    formatted.push(Node::Block(expanded.into_string()));
+}
+
+fn parse_capture(re_capture: &Regex, code: &str) -> Capture {
+    let captures = re_capture.captures(code).unwrap();
+    let strip = |s: &str| s.split_whitespace().collect::<String>();
+
+    Capture {
+        path: strip(&captures["path"]),
+        spec: captures.name("spec").map(|spec| strip(spec.as_str()))
+    }
+}
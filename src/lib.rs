@@ -10,6 +10,7 @@ specific language governing permissions and limitations under the License.
 */
 
 #![feature(proc_macro)]
+#![recursion_limit = "128"]
 
 extern crate base64_util;
 extern crate proc_macro;
@@ -20,10 +21,33 @@ extern crate rsx_images;
 extern crate rsx_parser;
 extern crate rsx_stylesheet;
 
-use std::env;
+mod assemble;
+mod component;
+mod compose;
+mod cfg;
+mod conditional;
+mod context;
+mod css;
+mod debug;
+mod elements;
+mod html;
+mod hydrate;
+mod i18n;
+mod interpolate;
+mod markdown;
+mod media;
+mod nesting;
+mod paths;
+mod raw;
+mod states;
+mod template;
+mod typed;
+mod variables;
+
 use std::fs::File;
 use std::io::Read;
 
+use quote::Tokens;
 use regex::Regex;
 use rsx_images::encoded::EncodedImage;
 use rsx_parser::parse as parse_rsx;
@@ -34,6 +58,16 @@ use rsx_stylesheet::types::Stylesheet;
 #[proc_macro]
 pub fn rsx(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let source = input.to_string();
+    let (dom, source) = paths::take_crate_path(&source, "::rsx_dom");
+    let (stylesheet, source) = paths::take_path_option(&source, "stylesheet", "::rsx_stylesheet");
+
+    let mut nodes = parse_template(&source);
+    paths::protect_blocks(&mut nodes);
+    template::map_elements(&mut nodes, &|element: template::Element| cfg::lower(elements::lower(element), &lower_element));
+    cfg::lower_root(&mut nodes);
+    template::wrap_root_blocks(&mut nodes);
+
+    let source = template::to_source(&nodes);
     let (ast, _) = parse_rsx(&source).unwrap();
 
     let expanded = quote! {
@@ -42,68 +76,323 @@ pub fn rsx(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         }
     };
 
+    let expanded = paths::qualify_dom(&expanded, &format!("{}::types", dom));
+    let expanded = paths::qualify_stylesheet(&expanded, &format!("{}::types", stylesheet));
+
+    debug::dump("rsx", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro_attribute]
+pub fn component(args: proc_macro::TokenStream, input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let options = component::parse_options(&args.to_string());
+    if options.is_empty() {
+        return input;
+    }
+
+    let source = input.to_string();
+    let mut expanded = if options.iter().any(|option| option == "memo") {
+        component::expand_memo(&component::parse_component(&source))
+    } else {
+        let mut tokens = Tokens::new();
+        tokens.append(&source);
+        tokens
+    };
+    if options.iter().any(|option| option == "hydrate") {
+        expanded.append_all(&[hydrate::expand_component(&source)]);
+    }
+
+    debug::dump("component", &expanded);
+
     expanded.parse().unwrap()
 }
 
 #[proc_macro]
-pub fn css(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+pub fn rsx_context(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let source = input.to_string();
-    let len = source.len();
+    let (stylesheet, source) = paths::take_path_option(&source, "stylesheet", "::rsx_stylesheet");
+    if !source.trim().is_empty() {
+        panic!("`rsx_context!` only takes a `stylesheet = path,` argument");
+    }
 
-    let origin = Origin::UserAgent;
-    let qm = QuirksMode::NoQuirks;
-    let media = MediaList::empty();
-    let url = Url::parse("about::inline").unwrap();
+    let expanded = context::expand_module();
+    let expanded = paths::qualify_stylesheet(&expanded, &format!("{}::types", stylesheet));
+
+    debug::dump("rsx_context", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro]
+pub fn define_element(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = input.to_string();
+    let (stylesheet_crate, source) = paths::take_crate_path(&source, "::rsx_stylesheet");
+    let mut definition = elements::parse_definition(&source);
+    if let Some(style) = css::group_after(input, "style") {
+        definition.style = css::source_from_tokens(style).0;
+    }
 
-    let css = if source.chars().nth(0).unwrap() == '"' {
-        let file_path = env::current_dir().unwrap().join(&source[1..len - 1]);
-
-        let mut file_contents = String::new();
-        File::open(&file_path)
-            .expect(&format!(
-                "Couldn't open file {}",
-                file_path.to_string_lossy()
-            ))
-            .read_to_string(&mut file_contents)
-            .expect(&format!(
-                "Couldn't read file {}",
-                file_path.to_string_lossy()
-            ));
-
-        file_contents
+    let css = elements::default_style_source(&definition);
+    let stylesheet = parse_stylesheet(&css);
+
+    let types = format!("{}::types", stylesheet_crate);
+    let stylesheet = paths::qualify_all(&quote! { #stylesheet }, &types);
+
+    let expanded = elements::expand_definition(&definition, &stylesheet, &types);
+
+    debug::dump("define_element", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro]
+pub fn markdown(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = input.to_string();
+
+    let mut contents = None;
+    let mut dependencies = vec![];
+    let mut dom = "::rsx_dom".to_string();
+    let mut elements = markdown::Elements::default();
+
+    for arg in template::split_top_level(&source) {
+        let (key, value) = match arg.find('=') {
+            Some(eq) => (arg[..eq].trim().to_string(), arg[eq + 1..].trim().to_string()),
+            None => ("path".to_string(), arg)
+        };
+        if key == "crate" {
+            dom = value.split_whitespace().collect();
+            continue;
+        }
+        let value = template::unquote(&value).expect(&format!("Expected a string literal for `{}`", key));
+
+        match key.as_str() {
+            "inline" => contents = Some(value),
+            "path" => {
+                let file_path = paths::resolve(&value);
+                contents = Some(css::read_file(&file_path));
+                dependencies.push(file_path);
+            }
+            kind => elements.set(kind, &value)
+        }
+    }
+
+    let contents = contents.expect("Expected a Markdown file path or `inline = \"...\"` source");
+    let nodes = markdown::to_nodes(&contents, &elements);
+
+    let source = template::to_source(&nodes);
+    let (ast, _) = parse_rsx(&source).unwrap();
+
+    let expanded = quote! {
+        fragment! {
+            #ast
+        }
+    };
+
+    let expanded = paths::qualify_dom(&expanded, &format!("{}::types", dom));
+    let dependencies = paths::expand_dependencies(&dependencies);
+
+    let expanded = quote! {
+        {
+            #dependencies
+            #expanded
+        }
+    };
+
+    debug::dump("markdown", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro]
+pub fn html(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = input.to_string();
+    let (dom, source) = paths::take_crate_path(&source, "::rsx_dom");
+
+    let html = template::unquote(&source).expect("Expected a string literal with the HTML source");
+    let mut nodes = raw::parse_html(&html);
+    if nodes.len() != 1 {
+        nodes = vec![template::fragment(nodes)];
+    }
+
+    let source = template::to_source(&nodes);
+    let (ast, _) = parse_rsx(&source).unwrap();
+
+    let expanded = quote! {
+        fragment! {
+            #ast
+        }
+    };
+
+    let expanded = paths::qualify_dom(&expanded, &format!("{}::types", dom));
+
+    debug::dump("html", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro]
+pub fn rsx_html(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = input.to_string();
+
+    let re_hydrate = Regex::new(r"^\s*hydrate\s*,").unwrap();
+    let hydrate = re_hydrate.is_match(&source);
+
+    let (stylesheet, source) = paths::take_path_option(&re_hydrate.replace(&source, ""), "stylesheet", "::rsx_stylesheet");
+
+    let mut nodes = parse_template(&source);
+    if cfg::has_markers(&nodes) {
+        panic!("Conditional compilation markers aren't supported in `rsx_html!`");
+    }
+    let mut writer = if hydrate {
+        hydrate::mark(&mut nodes);
+        html::Writer::with_markers()
     } else {
-        // When converting TokenStreams to Strings, a whitespace is inserted between
-        // each and every token. This unfortunately means that whitespace is also
-        // inserted in selectors, turning ".foo" into ". foo" which isn't valid CSS.
-        // Same goes to rule names, such as "margin-left" becoming "margin - left".
-        // Crudely find and fix those occurrences.
+        html::Writer::new()
+    };
+    writer.write_nodes(&nodes);
 
-        let re_selectors = Regex::new(r"(?P<type>[.#])\s(?P<name>[a-zA-Z0-9]+)").unwrap();
-        let file_contents = re_selectors.replace_all(&source, "$type$name");
+    let expanded = writer.expand();
+    let expanded = paths::qualify_stylesheet(&expanded, &format!("{}::types", stylesheet));
 
-        let re_rules = Regex::new(r"(?P<start>[a-zA-Z0-9]+)\s-\s(?P<end>[a-zA-Z0-9]+)\s").unwrap();
-        let file_contents = re_rules.replace_all(&file_contents, "$start-$end");
+    debug::dump("rsx_html", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro]
+pub fn rsx_hydrate(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = input.to_string();
+    let (dom, source) = paths::take_crate_path(&source, "::rsx_dom");
+    let (html, source) = hydrate::split_args(&source);
+    let (stylesheet, source) = paths::take_path_option(&source, "stylesheet", "::rsx_stylesheet");
 
-        file_contents.into_owned()
+    // A component's name expands to the macro holding its template.
+    if !source.trim().starts_with('<') {
+        let component = source.trim().rsplit("::").next().unwrap().trim();
+        let mut expanded = Tokens::new();
+        expanded.append(format!("{}!(crate = {}, {})", hydrate::macro_name(component), dom, html));
+
+        debug::dump("rsx_hydrate", &expanded);
+
+        return expanded.parse().unwrap();
+    }
+
+    let mut nodes = parse_template(&source);
+    if cfg::has_markers(&nodes) {
+        panic!("Conditional compilation markers aren't supported in `rsx_hydrate!`");
+    }
+    paths::protect_blocks(&mut nodes);
+    hydrate::mark(&mut nodes);
+    hydrate::restore(&mut nodes);
+
+    let source = template::to_source(&nodes);
+    let (ast, _) = parse_rsx(&source).unwrap();
+
+    let expanded = hydrate::expand(&html, &ast);
+    let expanded = paths::qualify_dom(&expanded, &format!("{}::types", dom));
+    let expanded = paths::qualify_stylesheet(&expanded, &format!("{}::types", stylesheet));
+
+    debug::dump("rsx_hydrate", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro]
+pub fn css(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = input.to_string();
+    let (stylesheet_crate, source) = paths::take_crate_path(&source, "::rsx_stylesheet");
+    let (lint_level, source) = css::take_lint_level(&source);
+    let (typed, source) = css::take_expression(&source, "typed");
+    let source = source.trim();
+    let len = source.len();
+
+    let (css, file_path, file, spans) = if source.chars().nth(0).unwrap() == '"' {
+        let file_path = paths::resolve(&source[1..len - 1]);
+        let file_contents = css::read_file(&file_path);
+        let file = file_path.to_string_lossy().into_owned();
+        (file_contents, Some(file_path), file, vec![])
+    } else {
+        let tokens = css::skip_options(input);
+        let file = css::inline_location(&tokens);
+        let (css, spans) = css::source_from_tokens(tokens);
+        (css, None, file, spans)
     };
 
-    let parsed = parse_css(&css, url, origin, qm, media);
-    let stylesheet: Stylesheet = parsed.into();
+    let mut stack = file_path.iter().map(|path| path.canonicalize().unwrap()).collect();
+    let mut imported = vec![];
+    let inlined = css::inline_imports(&css, file_path.as_ref().map(|path| path.as_path()), &mut stack, &mut imported);
+
+    let inlined = nesting::flatten(&inlined);
+    let variables = variables::collect(&inlined);
+
+    let css = variables::substitute(&nesting::flatten(&css), &variables);
+    css::report(css::check(&css, &parse_stylesheet), &file, &spans, lint_level);
+    for import in &imported {
+        let file = import.path.to_string_lossy();
+        let css = variables::substitute(&nesting::flatten(&import.css), &variables);
+        css::report(css::check(&css, &parse_stylesheet), &file, &[], lint_level);
+    }
+
+    let dependencies = file_path
+        .into_iter()
+        .chain(imported.into_iter().map(|import| import.path))
+        .collect::<Vec<_>>();
+    let dependencies = paths::expand_dependencies(&dependencies);
+
+    let css = variables::substitute(&inlined, &variables);
+    let rules = conditional::split(&css);
+    let is_conditional = conditional::is_conditional(&rules);
+    let stylesheet = if is_conditional {
+        conditional::expand(&rules, &parse_stylesheet)
+    } else {
+        let stylesheet = parse_stylesheet(&css);
+        quote! { #stylesheet }
+    };
+    let stylesheet = if typed::parse_option(typed) {
+        if is_conditional {
+            panic!("The `typed = true,` option can't be combined with `@media` rules or interactive states");
+        }
+        typed::expand(&stylesheet, &typed::class_names(&css))
+    } else {
+        stylesheet
+    };
 
     let expanded = quote! {
-        #stylesheet
+        {
+            #dependencies
+            #stylesheet
+        }
     };
 
+    let expanded = paths::qualify_all(&expanded, &format!("{}::types", stylesheet_crate));
+
+    debug::dump("css", &expanded);
+
+    expanded.parse().unwrap()
+}
+
+#[proc_macro]
+pub fn t(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let source = input.to_string();
+    let message = i18n::message_from_args(&source);
+
+    let expanded = i18n::expand(&message);
+
+    debug::dump("t", &expanded);
+
     expanded.parse().unwrap()
 }
 
 #[proc_macro]
 pub fn load_image(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let source = input.to_string();
+    let (resources_crate, source) = paths::take_crate_path(&source, "::rsx_images");
+    let source = source.trim();
     let len = source.len();
 
     let input_path = &source[1..len - 1];
-    let file_path = env::current_dir().unwrap().join(input_path);
+    let file_path = paths::resolve(input_path);
 
     let mut file_contents = Vec::new();
     File::open(&file_path)
@@ -120,7 +409,7 @@ pub fn load_image(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let format = EncodedImage::guess_format(&file_contents).unwrap();
     let size = EncodedImage::get_dimensions(format, &file_contents).unwrap();
     let data_uri = base64_util::to_image_data_uri(format.as_ref(), &file_contents);
-    let bytes_path = format!("../{}", input_path);
+    let bytes_path = file_path.to_string_lossy().into_owned();
 
     let expanded = quote! {
         EncodedImage::BytesAndDataUri {
@@ -131,16 +420,22 @@ pub fn load_image(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         }
     };
 
+    let expanded = paths::qualify_all(&expanded, &format!("{}::types", resources_crate));
+
+    debug::dump("load_image", &expanded);
+
     expanded.parse().unwrap()
 }
 
 #[proc_macro]
 pub fn load_font(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
     let source = input.to_string();
+    let (resources_crate, source) = paths::take_crate_path(&source, "::rsx_fonts");
+    let source = source.trim();
     let len = source.len();
 
     let input_path = &source[1..len - 1];
-    let file_path = env::current_dir().unwrap().join(input_path);
+    let file_path = paths::resolve(input_path);
 
     let mut file_contents = Vec::new();
     File::open(&file_path)
@@ -155,7 +450,7 @@ pub fn load_font(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         ));
 
     let data_uri = base64_util::to_font_data_uri(&file_contents);
-    let bytes_path = format!("../{}", input_path);
+    let bytes_path = file_path.to_string_lossy().into_owned();
 
     let expanded = quote! {
         EncodedFont::BytesAndDataUri {
@@ -164,5 +459,39 @@ pub fn load_font(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
         }
     };
 
+    let expanded = paths::qualify_all(&expanded, &format!("{}::types", resources_crate));
+
+    debug::dump("load_font", &expanded);
+
     expanded.parse().unwrap()
 }
+
+// Lowers the elements that are built by code rather than parsed as DOM nodes.
+fn lower_element(element: template::Element) -> template::Node {
+    match context::lower(element) {
+        template::Node::Element(element) => match component::lower(element) {
+            template::Node::Element(element) => assemble::lower(element),
+            node => node
+        },
+        node => node
+    }
+}
+
+fn parse_template(source: &str) -> Vec<template::Node> {
+    let mut nodes = template::parse(source).unwrap();
+    raw::expand_raw(&mut nodes);
+    i18n::translate_nodes(&mut nodes);
+    interpolate::format_text_nodes(&mut nodes);
+    compose::compose_attributes(&mut nodes);
+    nodes
+}
+
+fn parse_stylesheet(css: &str) -> Stylesheet {
+    let origin = Origin::UserAgent;
+    let qm = QuirksMode::NoQuirks;
+    let media = MediaList::empty();
+    let url = Url::parse("about::inline").unwrap();
+
+    let parsed = parse_css(css, url, origin, qm, media);
+    parsed.into()
+}
@@ -0,0 +1,253 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::collections::HashMap;
+
+use quote::Tokens;
+
+use template::{Attribute, AttributeValue, Element, Node};
+
+// Converts a Markdown document into template nodes, covering headings,
+// paragraphs, lists, emphasis, links, inline code and fenced code blocks.
+// Text is emitted as string literal code blocks, so that it doesn't have to be
+// valid RSX.
+
+pub struct Elements(HashMap<&'static str, String>);
+
+impl Default for Elements {
+    fn default() -> Self {
+        let mut names = HashMap::new();
+        for &(kind, name) in &[
+            ("root", "div"),
+            ("h1", "h1"),
+            ("h2", "h2"),
+            ("h3", "h3"),
+            ("h4", "h4"),
+            ("h5", "h5"),
+            ("h6", "h6"),
+            ("paragraph", "p"),
+            ("list", "ul"),
+            ("ordered_list", "ol"),
+            ("item", "li"),
+            ("emphasis", "em"),
+            ("strong", "strong"),
+            ("link", "a"),
+            ("code", "code"),
+            ("code_block", "pre"),
+        ] {
+            names.insert(kind, name.to_string());
+        }
+        Elements(names)
+    }
+}
+
+impl Elements {
+    // Overrides the element used for some kind of content. The `heading`
+    // kind applies to all six heading levels.
+    pub fn set(&mut self, kind: &str, name: &str) {
+        if kind == "heading" {
+            for level in &["h1", "h2", "h3", "h4", "h5", "h6"] {
+                self.0.insert(*level, name.to_string());
+            }
+            return;
+        }
+
+        let key = *self.0
+            .keys()
+            .find(|key| **key == kind)
+            .expect(&format!("Unknown Markdown element kind `{}`", kind));
+        self.0.insert(key, name.to_string());
+    }
+
+    fn get(&self, kind: &str) -> String {
+        self.0[kind].clone()
+    }
+}
+
+pub fn to_nodes(markdown: &str, elements: &Elements) -> Vec<Node> {
+    let mut blocks = vec![];
+    let mut paragraph: Vec<&str> = vec![];
+    let mut list: Option<(bool, Vec<String>)> = None;
+    let mut lines = markdown.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim();
+
+        if trimmed.starts_with("```") {
+            flush_paragraph(&mut paragraph, &mut blocks, elements);
+            flush_list(&mut list, &mut blocks, elements);
+            let mut code = vec![];
+            while let Some(line) = lines.next() {
+                if line.trim().starts_with("```") {
+                    break;
+                }
+                code.push(line);
+            }
+            blocks.push(element(&elements.get("code_block"), vec![], vec![text(&code.join("\n"))]));
+            continue;
+        }
+
+        if trimmed.is_empty() {
+            flush_paragraph(&mut paragraph, &mut blocks, elements);
+            flush_list(&mut list, &mut blocks, elements);
+            continue;
+        }
+
+        let level = trimmed.chars().take_while(|c| *c == '#').count();
+        if level > 0 && level <= 6 && trimmed[level..].starts_with(' ') {
+            flush_paragraph(&mut paragraph, &mut blocks, elements);
+            flush_list(&mut list, &mut blocks, elements);
+            let kind = format!("h{}", level);
+            blocks.push(element(&elements.get(&kind), vec![], inline(trimmed[level..].trim(), elements)));
+            continue;
+        }
+
+        if let Some((ordered, item)) = list_item(trimmed) {
+            flush_paragraph(&mut paragraph, &mut blocks, elements);
+            if list.as_ref().map_or(false, |&(o, _)| o != ordered) {
+                flush_list(&mut list, &mut blocks, elements);
+            }
+            list.get_or_insert_with(|| (ordered, vec![])).1.push(item.to_string());
+            continue;
+        }
+
+        if line.starts_with(' ') {
+            // Indented lines continue the current list item.
+            if let Some((_, ref mut items)) = list {
+                let last = items.last_mut().unwrap();
+                last.push(' ');
+                last.push_str(trimmed);
+                continue;
+            }
+        }
+
+        flush_list(&mut list, &mut blocks, elements);
+        paragraph.push(trimmed);
+    }
+
+    flush_paragraph(&mut paragraph, &mut blocks, elements);
+    flush_list(&mut list, &mut blocks, elements);
+
+    vec![element(&elements.get("root"), vec![], blocks)]
+}
+
+fn flush_paragraph(paragraph: &mut Vec<&str>, blocks: &mut Vec<Node>, elements: &Elements) {
+    if paragraph.is_empty() {
+        return;
+    }
+    let children = inline(&paragraph.join(" "), elements);
+    blocks.push(element(&elements.get("paragraph"), vec![], children));
+    paragraph.clear();
+}
+
+fn flush_list(list: &mut Option<(bool, Vec<String>)>, blocks: &mut Vec<Node>, elements: &Elements) {
+    if let Some((ordered, items)) = list.take() {
+        let items = items
+            .iter()
+            .map(|item| element(&elements.get("item"), vec![], inline(item, elements)))
+            .collect();
+        let kind = if ordered { "ordered_list" } else { "list" };
+        blocks.push(element(&elements.get(kind), vec![], items));
+    }
+}
+
+// Returns whether a line is an ordered list item, and its contents.
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    for marker in &["- ", "* ", "+ "] {
+        if line.starts_with(marker) {
+            return Some((false, line[2..].trim()));
+        }
+    }
+
+    let digits = line.chars().take_while(|c| c.is_digit(10)).count();
+    if digits > 0 && line[digits..].starts_with(". ") {
+        return Some((true, line[digits + 2..].trim()));
+    }
+
+    None
+}
+
+fn inline(source: &str, elements: &Elements) -> Vec<Node> {
+    let mut nodes = vec![];
+    let mut pending = String::new();
+    let mut rest = source;
+
+    while let Some(c) = rest.chars().next() {
+        let span = if rest.starts_with("**") {
+            delimited(rest, "**", "**").map(|(inner, len)| (element(&elements.get("strong"), vec![], inline(inner, elements)), len))
+        } else if c == '*' || c == '_' {
+            let delimiter = &rest[..1];
+            delimited(rest, delimiter, delimiter)
+                .map(|(inner, len)| (element(&elements.get("emphasis"), vec![], inline(inner, elements)), len))
+        } else if c == '`' {
+            delimited(rest, "`", "`").map(|(inner, len)| (element(&elements.get("code"), vec![], vec![text(inner)]), len))
+        } else if c == '[' {
+            link(rest).map(|(label, url, len)| {
+                let href = Attribute::Named("href".to_string(), Some(AttributeValue::Str(format!("{:?}", url))));
+                (element(&elements.get("link"), vec![href], inline(label, elements)), len)
+            })
+        } else {
+            None
+        };
+
+        match span {
+            Some((node, len)) => {
+                if !pending.is_empty() {
+                    nodes.push(text(&pending));
+                    pending.clear();
+                }
+                nodes.push(node);
+                rest = &rest[len..];
+            }
+            None => {
+                pending.push(c);
+                rest = &rest[c.len_utf8()..];
+            }
+        }
+    }
+
+    if !pending.is_empty() {
+        nodes.push(text(&pending));
+    }
+
+    nodes
+}
+
+// Returns the contents between the delimiters and the length of the span.
+fn delimited<'a>(source: &'a str, open: &str, close: &str) -> Option<(&'a str, usize)> {
+    let end = source[open.len()..].find(close)?;
+    if end == 0 {
+        return None;
+    }
+    Some((&source[open.len()..open.len() + end], open.len() + end + close.len()))
+}
+
+// Parses `[label](url)`, returning the label, url and the length of the span.
+fn link(source: &str) -> Option<(&str, &str, usize)> {
+    let label_end = source.find("](")?;
+    let url_end = source[label_end + 2..].find(')')? + label_end + 2;
+    Some((&source[1..label_end], &source[label_end + 2..url_end], url_end + 1))
+}
+
+fn element(name: &str, attributes: Vec<Attribute>, children: Vec<Node>) -> Node {
+    Node::Element(Element {
+        name: name.to_string(),
+        attributes,
+        self_closing: children.is_empty(),
+        children
+    })
+}
+
+fn text(value: &str) -> Node {
+    let mut tokens = Tokens::new();
+    tokens.append_all(&[value]);
+    Node::Block(tokens.into_string())
+}
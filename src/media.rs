@@ -0,0 +1,69 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use regex::Regex;
+
+// The servo parser is given an empty media list, and `Stylesheet` has no room
+// for media conditions, so `@media` rules are kept apart from the other rules
+// with their query compiled into a condition on the viewport size, to be
+// evaluated at runtime, such as after the window is resized.
+
+// Returns the condition of a `@media` rule's prelude, or `None` for other
+// at-rules.
+pub fn condition_of(prelude: &str) -> Option<String> {
+    let re_media = Regex::new(r"(?i)^@media\s+(?P<query>.*)$").unwrap();
+    re_media.captures(prelude.trim()).map(|captures| condition(captures["query"].trim()))
+}
+
+// Converts a media query list into an expression on `__rsx_viewport`.
+fn condition(query_list: &str) -> String {
+    let queries = query_list.split(',').map(|query| {
+        let mut conditions = vec![];
+        for part in query.split(" and ").map(str::trim) {
+            let part = part.trim_left_matches("only ").trim();
+            match part.to_lowercase().as_str() {
+                "all" | "screen" => conditions.push("true".to_string()),
+                part if part.starts_with('(') && part.ends_with(')') => conditions.push(feature(&part[1..part.len() - 1])),
+                part => panic!("Unsupported media query `{}`, expected `screen` or `(feature: value)`", part)
+            }
+        }
+        format!("({})", conditions.join(" && "))
+    });
+    queries.collect::<Vec<_>>().join(" || ")
+}
+
+fn feature(feature: &str) -> String {
+    let mut parts = feature.splitn(2, ':').map(str::trim);
+    let name = parts.next().unwrap();
+    let value = parts.next().unwrap_or_else(|| panic!("Expected a value for the `{}` media feature", name));
+
+    if name == "orientation" {
+        return match value {
+            "landscape" => "__rsx_viewport.0 >= __rsx_viewport.1".to_string(),
+            "portrait" => "__rsx_viewport.0 < __rsx_viewport.1".to_string(),
+            value => panic!("Unknown orientation `{}`, expected `landscape` or `portrait`", value)
+        };
+    }
+
+    let length = value
+        .trim_right_matches("px")
+        .trim()
+        .parse::<f32>()
+        .unwrap_or_else(|_| panic!("Expected a length in pixels for `{}`, found `{}`", name, value));
+    let (dimension, operator) = match name {
+        "min-width" => (0, ">="),
+        "max-width" => (0, "<="),
+        "min-height" => (1, ">="),
+        "max-height" => (1, "<="),
+        name => panic!("Unsupported media feature `{}`", name)
+    };
+    format!("__rsx_viewport.{} {} {:?}f32", dimension, operator, length)
+}
@@ -0,0 +1,131 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use css;
+
+// Nested rules are flattened into plain rules before conversion, since the
+// servo parser only knows about the flat syntax. A nested selector replaces
+// each `&` with its parent selector, or is a descendant of it when there's no
+// `&`. The parent rule is closed before a nested rule and reopened after it
+// for any declarations that follow, so the declarations keep their order in
+// the cascade. Line breaks are kept, so that diagnostics still point at the
+// right line.
+
+pub fn flatten(css: &str) -> String {
+    let css = css::strip_comments(css);
+    let mut flattened = String::new();
+    let mut pos = 0;
+
+    while let Some(open) = css[pos..].find('{').map(|open| pos + open) {
+        let statement_start = css::prelude_start(&css, pos, open);
+        flattened.push_str(&css[pos..statement_start]);
+
+        let prelude = &css[statement_start..open];
+        let close = css::matching_brace(&css, open).expect("Unbalanced braces in stylesheet");
+        let body = &css[open + 1..close];
+
+        if prelude.trim().starts_with('@') {
+            flattened.push_str(&format!("{}{{{}}}", prelude, flatten(body)));
+        } else {
+            flatten_rule(prelude.trim(), prelude, body, &mut flattened);
+        }
+        pos = close + 1;
+    }
+
+    flattened.push_str(&css[pos..]);
+    flattened
+}
+
+fn flatten_rule(selector: &str, prelude: &str, body: &str, flattened: &mut String) {
+    if !body.contains('{') {
+        flattened.push_str(&format!("{}{{{}}}", prelude, body));
+        return;
+    }
+
+    // Only open the rule once there's a declaration, to avoid empty rules.
+    flattened.push_str(&lines(prelude));
+    let mut open = false;
+    let mut pos = 0;
+
+    while pos < body.len() {
+        let rest = &body[pos..];
+        let skipped = rest.len() - rest.trim_left().len();
+        flattened.push_str(&rest[..skipped]);
+        pos += skipped;
+        if pos >= body.len() {
+            break;
+        }
+
+        let rest = &body[pos..];
+        match statement_end(rest) {
+            (end, false) => {
+                if !open {
+                    flattened.push_str(&format!("{} {{ ", selector));
+                    open = true;
+                }
+                flattened.push_str(&rest[..end]);
+                pos += end;
+            }
+            (brace, true) => {
+                if open {
+                    flattened.push_str("} ");
+                    open = false;
+                }
+                let nested_prelude = &rest[..brace];
+                if nested_prelude.trim().starts_with('@') {
+                    panic!("At-rules can't be nested in rules, found `{}`", nested_prelude.trim());
+                }
+                let close = css::matching_brace(rest, brace).unwrap_or_else(|| panic!("Unbalanced braces in `{}`", rest.trim()));
+                let nested = combine(selector, nested_prelude.trim());
+                flatten_rule(&nested, nested_prelude, &rest[brace + 1..close], flattened);
+                pos += close + 1;
+            }
+        }
+    }
+
+    if open {
+        flattened.push_str("}");
+    }
+}
+
+// Returns the end of the declaration at the start of `source`, including its
+// semicolon, or the offset of the opening brace if it's a nested rule.
+fn statement_end(source: &str) -> (usize, bool) {
+    let mut depth = 0;
+    for (index, c) in source.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' if depth == 0 => return (index + 1, false),
+            '{' if depth == 0 => return (index, true),
+            _ => {}
+        }
+    }
+    (source.len(), false)
+}
+
+fn combine(parents: &str, selector: &str) -> String {
+    let mut combined = vec![];
+    for parent in parents.split(',').map(str::trim) {
+        for nested in selector.split(',').map(str::trim) {
+            if nested.contains('&') {
+                combined.push(nested.replace('&', parent));
+            } else {
+                combined.push(format!("{} {}", parent, nested));
+            }
+        }
+    }
+    combined.join(", ")
+}
+
+fn lines(source: &str) -> String {
+    source.chars().filter(|&c| c == '\n').collect()
+}
@@ -0,0 +1,233 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::env;
+use std::path::{Path, PathBuf};
+
+use proc_macro::Span;
+use quote::Tokens;
+use regex::Regex;
+
+use template::{Attribute, AttributeValue, Node};
+
+// The parser and stylesheet crates tokenize into bare type names, such as
+// `DOMNode::from(...)`, and `rsx!` used to wrap them in `fragment! { ... }`.
+// That required callers to glob-import the types and `#[macro_use]` rsx-dom.
+// Instead, the generated code is qualified with the path of the crate that
+// provides those types, which can be overridden with a leading `crate = path,`
+// argument for crates re-exporting them under another name. Code blocks
+// written by the caller are left as they are.
+
+// Generated code that's put in code blocks, such as composed style lists,
+// names the stylesheet types with this prefix, replaced by `qualify_stylesheet`
+// with the path given as `stylesheet = path,`.
+pub const STYLESHEET_TYPES: &str = "__rsx_stylesheet_types";
+
+pub const DOM_NAMES: &[&str] = &[
+    "DOMTree",
+    "DOMNode",
+    "DOMText",
+    "DOMTagName",
+    "KnownElementName",
+    "DOMAttribute",
+    "DOMAttributeName",
+    "KnownAttributeName",
+    "DOMAttributeValue"
+];
+
+const UNQUALIFIED: &str = "__rsx_unqualified_";
+
+const PRELUDE_NAMES: &[&str] = &["Some", "None", "Ok", "Err", "Vec", "String", "Box", "Option", "Result", "Default", "From", "Into"];
+
+// Resolves a path given to a macro. Paths starting with `./` or `../` are
+// relative to the file invoking the macro, and other paths to the root of the
+// crate being compiled, so that they don't depend on where cargo runs rustc.
+pub fn resolve(path: &str) -> PathBuf {
+    let path = Path::new(path);
+    if path.is_absolute() {
+        return path.to_path_buf();
+    }
+
+    let cwd = env::current_dir().unwrap();
+    if path.starts_with(".") || path.starts_with("..") {
+        let file = cwd.join(call_site_file());
+        return file.parent().unwrap_or(&cwd).join(path);
+    }
+
+    match env::var("CARGO_MANIFEST_DIR") {
+        Ok(dir) => PathBuf::from(dir).join(path),
+        Err(_) => cwd.join(path)
+    }
+}
+
+fn call_site_file() -> PathBuf {
+    PathBuf::from(Span::call_site().source_file().as_str())
+}
+
+// Cargo only knows about the files rustc reads itself, so include every file
+// a macro read, which makes edits to them trigger a rebuild.
+pub fn expand_dependencies(files: &[PathBuf]) -> Tokens {
+    let files = files.iter().map(|file| file.to_string_lossy().into_owned());
+    quote! {
+        #(let _ = include_str!(#files);)*
+    }
+}
+
+// Returns the crate path given as `crate = path,` and the remaining input.
+pub fn take_crate_path(source: &str, default: &str) -> (String, String) {
+    take_path_option(source, "crate", default)
+}
+
+// Same as `take_crate_path`, for a path given as `<name> = path,`.
+pub fn take_path_option(source: &str, name: &str, default: &str) -> (String, String) {
+    let re_option = Regex::new(&format!(
+        r"^\s*{}\s*=\s*(?P<path>(?:::\s*)?[a-zA-Z_][a-zA-Z0-9_]*(?:\s*::\s*[a-zA-Z_][a-zA-Z0-9_]*)*)\s*,",
+        name
+    )).unwrap();
+
+    match re_option.captures(source) {
+        Some(captures) => {
+            let path = captures["path"].split_whitespace().collect::<String>();
+            let rest = source[captures.get(0).unwrap().end()..].to_string();
+            (path, rest)
+        }
+        None => (default.to_string(), source.to_string())
+    }
+}
+
+// Hides the DOM names and `fragment!` invocations in the caller's code blocks
+// from `qualify_dom`, which is given the parser's output with those blocks in
+// it. Must run before any pass adds generated code to the blocks.
+pub fn protect_blocks(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        match *node {
+            Node::Element(ref mut element) => {
+                for attribute in &mut element.attributes {
+                    match *attribute {
+                        Attribute::Named(_, Some(AttributeValue::Block(ref mut code))) | Attribute::Spread(ref mut code) => {
+                            *code = protect(code);
+                        }
+                        _ => {}
+                    }
+                }
+                protect_blocks(&mut element.children);
+            }
+            Node::Block(ref mut code) => *code = protect(code),
+            Node::Text(_) => {}
+        }
+    }
+}
+
+fn protect(code: &str) -> String {
+    let names = DOM_NAMES.join("|");
+    let re_name = Regex::new(&format!(r"\b(?P<name>{}|fragment)\b", names)).unwrap();
+    re_name.replace_all(code, format!("{}${{name}}", UNQUALIFIED).as_str()).into_owned()
+}
+
+// Qualifies the given type names with `module`. Nested `fragment! { ... }`
+// invocations are turned into `DOMTree::from(...)` conversions, using the
+// same module. Names hidden by `protect_blocks` are restored as written.
+pub fn qualify_dom(tokens: &Tokens, module: &str) -> Tokens {
+    let qualified = qualify(tokens.as_str(), module, &|name: &str| DOM_NAMES.contains(&name));
+    let mut tokens = Tokens::new();
+    tokens.append(qualified.replace(UNQUALIFIED, ""));
+    tokens
+}
+
+pub fn qualify_stylesheet(tokens: &Tokens, module: &str) -> Tokens {
+    let mut qualified = Tokens::new();
+    qualified.append(tokens.as_str().replace(STYLESHEET_TYPES, module));
+    qualified
+}
+
+// Qualifies every type-like name that isn't part of the prelude. Only used on
+// code generated entirely by this crate or its dependencies, never on code
+// blocks written by the caller.
+pub fn qualify_all(tokens: &Tokens, module: &str) -> Tokens {
+    let mut qualified = Tokens::new();
+    qualified.append(qualify(tokens.as_str(), module, &|name: &str| {
+        name.chars().next().map_or(false, |c| c.is_uppercase()) && !PRELUDE_NAMES.contains(&name)
+    }));
+    qualified
+}
+
+fn qualify(tokens: &str, module: &str, should_qualify: &Fn(&str) -> bool) -> String {
+    let mut qualified = String::new();
+    let mut rest = tokens;
+
+    while let Some(c) = rest.chars().next() {
+        if c == '"' || c == '\'' {
+            let len = if c == '"' { string_len(rest) } else { char_len(rest) };
+            qualified.push_str(&rest[..len]);
+            rest = &rest[len..];
+            continue;
+        }
+
+        if !(c.is_alphabetic() || c == '_') {
+            qualified.push(c);
+            rest = &rest[c.len_utf8()..];
+            continue;
+        }
+
+        let len = rest.find(|c: char| !(c.is_alphanumeric() || c == '_')).unwrap_or(rest.len());
+        let name = &rest[..len];
+        let after = rest[len..].trim_left();
+        let is_path_segment = {
+            let before = qualified.trim_right();
+            before.ends_with("::") || before.ends_with('.')
+        };
+
+        if name == "fragment" && after.starts_with('!') && !is_path_segment {
+            let group = after[1..].trim_left();
+            let close = ::css::matching_brace(group, 0).unwrap_or_else(|| panic!("Unbalanced group in `{}`", group));
+            let inner = qualify(&group[1..close], module, should_qualify);
+            qualified.push_str(&format!("{}::DOMTree::from({{ {} }})", module, inner));
+            rest = &group[close + 1..];
+            continue;
+        }
+
+        if !is_path_segment && !after.starts_with('!') && should_qualify(name) {
+            qualified.push_str(module);
+            qualified.push_str("::");
+        }
+        qualified.push_str(name);
+        rest = &rest[len..];
+    }
+
+    qualified
+}
+
+fn string_len(source: &str) -> usize {
+    let mut escaped = false;
+    for (index, c) in source.char_indices().skip(1) {
+        if escaped {
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            return index + 1;
+        }
+    }
+    source.len()
+}
+
+// Char literals are skipped whole, lifetimes only by their quote.
+fn char_len(source: &str) -> usize {
+    let mut chars = source.char_indices().skip(1);
+    match chars.next() {
+        Some((_, '\\')) => source[3..].find('\'').map_or(source.len(), |end| end + 4),
+        Some((index, c)) => {
+            let end = index + c.len_utf8();
+            if source[end..].starts_with('\'') { end + 1 } else { 1 }
+        }
+        None => 1
+    }
+}
@@ -0,0 +1,169 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::Tokens;
+use regex::{Captures, Regex};
+
+use template::{self, Attribute, AttributeValue, Node};
+
+// Raw HTML snippets, given as `<raw html="..." />` or to `html!("...")`, are
+// parsed with the template grammar at compile time. Only allowlisted elements
+// and attributes are kept: scripts and similar elements are dropped along with
+// their contents, event handlers and `javascript:` urls are removed, and any
+// other element is an error. Text is emitted as string literals, so a snippet
+// can never inject code.
+
+pub const RAW_ELEMENT: &str = "raw";
+
+const ALLOWED_ELEMENTS: &[&str] = &[
+    "view", "text", "image", "fragment", "div", "span", "p", "br", "hr", "b", "i", "u", "s", "em", "strong", "small", "sub", "sup", "code",
+    "pre", "blockquote", "a", "img", "ul", "ol", "li", "h1", "h2", "h3", "h4", "h5", "h6", "table", "thead", "tbody", "tr", "th", "td",
+];
+
+const DROPPED_ELEMENTS: &[&str] = &["script", "style", "iframe", "object", "embed", "noscript", "template"];
+
+const ALLOWED_ATTRIBUTES: &[&str] = &["href", "src", "alt", "title", "width", "height", "colspan", "rowspan"];
+
+const VOID_ELEMENTS: &[&str] = &["br", "hr", "img"];
+
+// Replaces `<raw html="..." />` elements with the nodes parsed from the HTML.
+pub fn expand_raw(nodes: &mut Vec<Node>) {
+    let mut expanded = vec![];
+
+    for node in nodes.drain(..) {
+        match node {
+            Node::Element(ref element) if element.name == RAW_ELEMENT => {
+                let html = match element.attribute("html") {
+                    Some(&AttributeValue::Str(ref literal)) => template::unquote(literal).unwrap(),
+                    _ => panic!("Expected a string literal `html` attribute on `<{}>`", RAW_ELEMENT)
+                };
+                expanded.extend(parse_html(&html));
+            }
+            Node::Element(mut element) => {
+                expand_raw(&mut element.children);
+                expanded.push(Node::Element(element));
+            }
+            node => expanded.push(node)
+        }
+    }
+
+    *nodes = expanded;
+}
+
+pub fn parse_html(html: &str) -> Vec<Node> {
+    let re_comments = Regex::new(r"(?s)<!--.*?-->|<!(?i:doctype)[^>]*>").unwrap();
+    let re_void = Regex::new(r"<\s*(?P<name>[a-zA-Z]+)(?P<attributes>[^<>]*?)\s*/?\s*>").unwrap();
+
+    let html = re_comments.replace_all(html, "");
+    let html = re_void.replace_all(&html, |captures: &Captures| {
+        let name = captures["name"].to_lowercase();
+        if VOID_ELEMENTS.contains(&name.as_str()) {
+            format!("<{}{} />", name, &captures["attributes"])
+        } else {
+            captures[0].to_string()
+        }
+    });
+
+    let mut nodes = template::parse_html(&html).unwrap_or_else(|error| panic!("Couldn't parse HTML `{}`: {}", html, error));
+    sanitize(&mut nodes);
+    nodes
+}
+
+fn sanitize(nodes: &mut Vec<Node>) {
+    let mut sanitized = vec![];
+
+    for node in nodes.drain(..) {
+        match node {
+            Node::Text(text) => sanitized.push(text_node(&decode_entities(&text))),
+            Node::Block(code) => panic!(
+                "Code blocks aren't allowed in raw HTML, found `{{ {} }}`. Use `&#123;` and `&#125;` for braces",
+                code
+            ),
+            Node::Element(mut element) => {
+                element.name = element.name.to_lowercase();
+                if DROPPED_ELEMENTS.contains(&element.name.as_str()) {
+                    continue;
+                }
+                if !ALLOWED_ELEMENTS.contains(&element.name.as_str()) {
+                    panic!(
+                        "The `<{}>` element can't be used in raw HTML. Allowed elements are: {}",
+                        element.name,
+                        ALLOWED_ELEMENTS.join(", ")
+                    );
+                }
+                element.attributes = element.attributes.into_iter().filter_map(sanitize_attribute).collect();
+                sanitize(&mut element.children);
+                element.self_closing = element.children.is_empty();
+                sanitized.push(Node::Element(element));
+            }
+        }
+    }
+
+    *nodes = sanitized;
+}
+
+fn sanitize_attribute(attribute: Attribute) -> Option<Attribute> {
+    let (name, value) = match attribute {
+        Attribute::Named(name, value) => (name.to_lowercase(), value),
+        Attribute::Spread(code) => panic!("Code blocks aren't allowed in raw HTML, found `{{ {} }}`", code)
+    };
+    if !ALLOWED_ATTRIBUTES.contains(&name.as_str()) {
+        return None;
+    }
+
+    let value = match value {
+        Some(AttributeValue::Str(ref literal)) => decode_entities(&template::unquote(literal).unwrap()),
+        Some(AttributeValue::Literal(ref literal)) => decode_entities(literal),
+        Some(AttributeValue::Block(ref code)) => panic!("Code blocks aren't allowed in raw HTML, found `{{ {} }}`", code),
+        None => return None
+    };
+
+    let scheme = value.trim().to_lowercase();
+    if scheme.starts_with("javascript:") || scheme.starts_with("vbscript:") {
+        return None;
+    }
+
+    Some(Attribute::Named(name, Some(AttributeValue::Str(format!("{:?}", value)))))
+}
+
+fn decode_entities(text: &str) -> String {
+    let re_entity = Regex::new(r"&(?:#(?P<dec>[0-9]+)|#[xX](?P<hex>[0-9a-fA-F]+)|(?P<name>[a-zA-Z]+));").unwrap();
+
+    re_entity
+        .replace_all(text, |captures: &Captures| {
+            let code = if let Some(dec) = captures.name("dec") {
+                u32::from_str_radix(dec.as_str(), 10).ok()
+            } else if let Some(hex) = captures.name("hex") {
+                u32::from_str_radix(hex.as_str(), 16).ok()
+            } else {
+                match &captures["name"] {
+                    "amp" => Some('&' as u32),
+                    "lt" => Some('<' as u32),
+                    "gt" => Some('>' as u32),
+                    "quot" => Some('"' as u32),
+                    "apos" => Some('\'' as u32),
+                    "nbsp" => Some('\u{a0}' as u32),
+                    _ => None
+                }
+            };
+            match code.and_then(::std::char::from_u32) {
+                Some(c) => c.to_string(),
+                None => captures[0].to_string()
+            }
+        })
+        .into_owned()
+}
+
+fn text_node(value: &str) -> Node {
+    let mut tokens = Tokens::new();
+    tokens.append_all(&[value]);
+    Node::Block(tokens.into_string())
+}
@@ -0,0 +1,48 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use regex::Regex;
+
+// Rules with interactive pseudo-classes, such as `.button:hover`, are kept
+// apart from the others, with the pseudo-classes removed from their selectors
+// and replaced by the state bits they require, one bit per pseudo-class. A
+// node's declarations for the states it's in are then looked up at runtime.
+
+pub const STATES: &[(&str, u32)] = &[("hover", 1), ("active", 2), ("focus", 4), ("disabled", 8)];
+
+// Groups the selectors of a rule by the states they require.
+pub fn split_selectors(prelude: &str) -> Vec<(u32, String)> {
+    let mut groups: Vec<(u32, Vec<String>)> = vec![];
+
+    for selector in prelude.split(',').map(str::trim) {
+        let mut state = 0;
+        let mut stripped = selector.to_string();
+        for &(name, bit) in STATES {
+            if pseudo_class(name).is_match(&stripped) {
+                state |= bit;
+                stripped = pseudo_class(name).replace_all(&stripped, "$end").into_owned();
+            }
+        }
+        match groups.iter().position(|&(existing, _)| existing == state) {
+            Some(index) => groups[index].1.push(stripped),
+            None => groups.push((state, vec![stripped]))
+        }
+    }
+
+    groups
+        .into_iter()
+        .map(|(state, selectors)| (state, selectors.join(", ")))
+        .collect()
+}
+
+fn pseudo_class(name: &str) -> Regex {
+    Regex::new(&format!(r":{}(?P<end>[^-\w]|$)", name)).unwrap()
+}
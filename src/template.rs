@@ -0,0 +1,599 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+// A lightweight tree over the stringified `rsx!` input. It only understands
+// enough of the JSX grammar to tell elements, attributes, text and code blocks
+// apart, so that source level passes can rewrite a template before it's handed
+// over to `rsx_parser`. Code blocks are kept verbatim.
+
+// Conditional compilation markers are kept as attributes until the `cfg`
+// pass lowers them: `cfg(predicate)` on an element, or `#[cfg(predicate)]`
+// before it, gates the element, while `#[cfg(predicate)]` inside a tag gates
+// the attribute following it.
+pub const CFG: &str = "cfg";
+pub const CFG_MARKER: &str = "#[cfg]";
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Node {
+    Element(Element),
+    Text(String),
+    Block(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Element {
+    pub name: String,
+    pub attributes: Vec<Attribute>,
+    pub children: Vec<Node>,
+    pub self_closing: bool
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Attribute {
+    Named(String, Option<AttributeValue>),
+    Spread(String)
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum AttributeValue {
+    Str(String),
+    Literal(String),
+    Block(String)
+}
+
+impl Element {
+    // Returns the name without the type arguments of a generic component.
+    pub fn base_name(&self) -> &str {
+        self.name.split('<').next().unwrap().trim()
+    }
+
+    pub fn attribute(&self, name: &str) -> Option<&AttributeValue> {
+        self.attributes
+            .iter()
+            .filter_map(|attribute| match *attribute {
+                Attribute::Named(ref n, ref value) if n == name => value.as_ref(),
+                _ => None
+            })
+            .next()
+    }
+}
+
+// Turns a string literal as found in the source back into its value.
+pub fn unquote(literal: &str) -> Option<String> {
+    let literal = literal.trim();
+    if literal.starts_with('r') {
+        let hashes = literal[1..].chars().take_while(|c| *c == '#').count();
+        let inner = &literal[1 + hashes..literal.len() - hashes];
+        if inner.len() < 2 || !inner.starts_with('"') || !inner.ends_with('"') {
+            return None;
+        }
+        return Some(inner[1..inner.len() - 1].to_string());
+    }
+    if literal.len() < 2 || !literal.starts_with('"') || !literal.ends_with('"') {
+        return None;
+    }
+
+    let mut value = String::new();
+    let mut chars = literal[1..literal.len() - 1].chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            value.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') => value.push('\n'),
+            Some('r') => value.push('\r'),
+            Some('t') => value.push('\t'),
+            Some('0') => value.push('\0'),
+            Some(c) => value.push(c),
+            None => {}
+        }
+    }
+
+    Some(value)
+}
+
+// Splits macro arguments on the commas that aren't nested in any group.
+pub fn split_top_level(source: &str) -> Vec<String> {
+    let mut parts = vec![];
+    let mut current = String::new();
+    let mut depth = 0;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for c in source.chars() {
+        if in_string {
+            current.push(c);
+            if escaped {
+                escaped = false;
+            } else if c == '\\' {
+                escaped = true;
+            } else if c == '"' {
+                in_string = false;
+            }
+            continue;
+        }
+        match c {
+            '"' => in_string = true,
+            '(' | '[' | '{' => depth += 1,
+            ')' | ']' | '}' => depth -= 1,
+            ',' if depth == 0 => {
+                parts.push(current.trim().to_string());
+                current.clear();
+                continue;
+            }
+            _ => {}
+        }
+        current.push(c);
+    }
+
+    if !current.trim().is_empty() {
+        parts.push(current.trim().to_string());
+    }
+
+    parts
+}
+
+pub fn parse(source: &str) -> Result<Vec<Node>, String> {
+    parse_source(source, false)
+}
+
+// Parses HTML, where unlike tokens stringified by rustc, whitespace between
+// words and elements is significant and attribute values can be single-quoted.
+pub fn parse_html(source: &str) -> Result<Vec<Node>, String> {
+    parse_source(source, true)
+}
+
+fn parse_source(source: &str, html: bool) -> Result<Vec<Node>, String> {
+    let mut parser = Parser { source, pos: 0, html };
+    let nodes = parser.parse_nodes(None)?;
+    parser.skip_whitespace();
+    if parser.pos < source.len() {
+        return Err(format!("Unexpected input at {}", parser.pos));
+    }
+    Ok(nodes)
+}
+
+pub fn fragment(children: Vec<Node>) -> Node {
+    Node::Element(Element {
+        name: "fragment".to_string(),
+        attributes: vec![],
+        self_closing: children.is_empty(),
+        children
+    })
+}
+
+// Replaces every element with what `f` returns for it, children first.
+pub fn map_elements(nodes: &mut Vec<Node>, f: &Fn(Element) -> Node) {
+    for node in nodes.iter_mut() {
+        let mapped = match *node {
+            Node::Element(ref mut element) => {
+                map_elements(&mut element.children, f);
+                Some(f(element.clone()))
+            }
+            _ => None
+        };
+        if let Some(mapped) = mapped {
+            *node = mapped;
+        }
+    }
+}
+
+// Passes lowering elements into code blocks can leave a block at the root,
+// while the template needs a root element, so keep those in a fragment.
+pub fn wrap_root_blocks(nodes: &mut Vec<Node>) {
+    for node in nodes.iter_mut() {
+        if let Node::Block(_) = *node {
+            let block = node.clone();
+            *node = fragment(vec![block]);
+        }
+    }
+}
+
+pub fn to_source(nodes: &[Node]) -> String {
+    let mut source = String::new();
+    for node in nodes {
+        write_node(node, &mut source);
+    }
+    source
+}
+
+fn write_node(node: &Node, source: &mut String) {
+    match *node {
+        Node::Text(ref text) => {
+            source.push_str(text);
+            source.push(' ');
+        }
+        Node::Block(ref code) => {
+            source.push_str("{ ");
+            source.push_str(code);
+            source.push_str(" } ");
+        }
+        Node::Element(ref element) => {
+            source.push('<');
+            source.push_str(&element.name);
+            for attribute in &element.attributes {
+                source.push(' ');
+                match *attribute {
+                    Attribute::Named(ref name, ref value) => {
+                        source.push_str(name);
+                        match *value {
+                            Some(AttributeValue::Str(ref s)) | Some(AttributeValue::Literal(ref s)) => {
+                                source.push('=');
+                                source.push_str(s);
+                            }
+                            Some(AttributeValue::Block(ref code)) => {
+                                source.push_str("={ ");
+                                source.push_str(code);
+                                source.push_str(" }");
+                            }
+                            None => {}
+                        }
+                    }
+                    Attribute::Spread(ref code) => {
+                        source.push_str("{ ");
+                        source.push_str(code);
+                        source.push_str(" }");
+                    }
+                }
+            }
+            if element.self_closing {
+                source.push_str(" /> ");
+            } else {
+                source.push_str("> ");
+                for child in &element.children {
+                    write_node(child, source);
+                }
+                source.push_str("</");
+                source.push_str(element.base_name());
+                source.push_str("> ");
+            }
+        }
+    }
+}
+
+struct Parser<'a> {
+    source: &'a str,
+    pos: usize,
+    html: bool
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<char> {
+        self.source[self.pos..].chars().next()
+    }
+
+    fn bump(&mut self) -> Option<char> {
+        let c = self.peek();
+        if let Some(c) = c {
+            self.pos += c.len_utf8();
+        }
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while let Some(c) = self.peek() {
+            if !c.is_whitespace() {
+                break;
+            }
+            self.bump();
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.bump() {
+            Some(c) if c == expected => Ok(()),
+            Some(c) => Err(format!("Expected `{}` but found `{}` at {}", expected, c, self.pos)),
+            None => Err(format!("Expected `{}` but reached the end of input", expected))
+        }
+    }
+
+    fn parse_nodes(&mut self, closing: Option<&str>) -> Result<Vec<Node>, String> {
+        let mut nodes = vec![];
+        let mut text = String::new();
+        let mut cfg = None;
+
+        loop {
+            match self.peek() {
+                None => {
+                    self.flush_text(&mut text, &mut nodes);
+                    if cfg.is_some() {
+                        return Err("Expected an element after `#[cfg(...)]`".to_string());
+                    }
+                    return match closing {
+                        Some(name) => Err(format!("Unclosed element `{}`", name)),
+                        None => Ok(nodes)
+                    };
+                }
+                Some('#') if !self.html && self.at_cfg_marker() => {
+                    self.flush_text(&mut text, &mut nodes);
+                    cfg = Some(self.parse_cfg_marker()?);
+                }
+                Some('{') => {
+                    self.flush_text(&mut text, &mut nodes);
+                    if cfg.is_some() {
+                        return Err("Expected an element after `#[cfg(...)]`".to_string());
+                    }
+                    let code = self.parse_block()?;
+                    nodes.push(Node::Block(code));
+                }
+                Some('<') => {
+                    self.flush_text(&mut text, &mut nodes);
+                    self.bump();
+                    self.skip_whitespace();
+                    if self.peek() == Some('/') {
+                        self.bump();
+                        let name = self.parse_name()?;
+                        self.expect('>')?;
+                        return match closing {
+                            Some(expected) if expected == name => Ok(nodes),
+                            _ => Err(format!("Unexpected closing element `{}`", name))
+                        };
+                    }
+                    let mut element = self.parse_element()?;
+                    if let Some(predicate) = cfg.take() {
+                        element.attributes.push(Attribute::Named(CFG.to_string(), Some(AttributeValue::Block(predicate))));
+                    }
+                    nodes.push(Node::Element(element));
+                }
+                Some(c) => {
+                    text.push(c);
+                    self.bump();
+                }
+            }
+        }
+    }
+
+    fn parse_element(&mut self) -> Result<Element, String> {
+        let base_name = self.parse_name()?;
+        let mut name = base_name.clone();
+        let mut attributes = vec![];
+
+        self.skip_whitespace();
+        if self.peek() == Some('<') {
+            let arguments = self.parse_group('<', '>')?;
+            name = format!("{}<{}>", base_name, arguments);
+        }
+
+        loop {
+            self.skip_whitespace();
+            match self.peek() {
+                Some('/') => {
+                    self.bump();
+                    self.expect('>')?;
+                    return Ok(Element {
+                        name,
+                        attributes,
+                        children: vec![],
+                        self_closing: true
+                    });
+                }
+                Some('>') => {
+                    self.bump();
+                    let children = self.parse_nodes(Some(&base_name))?;
+                    return Ok(Element {
+                        name,
+                        attributes,
+                        children,
+                        self_closing: false
+                    });
+                }
+                Some('{') => {
+                    let code = self.parse_block()?;
+                    attributes.push(Attribute::Spread(code));
+                }
+                Some('#') => {
+                    let predicate = self.parse_cfg_marker()?;
+                    attributes.push(Attribute::Named(CFG_MARKER.to_string(), Some(AttributeValue::Block(predicate))));
+                }
+                Some(_) => {
+                    let attribute_name = self.parse_name()?;
+                    self.skip_whitespace();
+                    if attribute_name == CFG && self.peek() == Some('(') {
+                        let predicate = self.parse_group('(', ')')?;
+                        attributes.push(Attribute::Named(CFG.to_string(), Some(AttributeValue::Block(predicate))));
+                        continue;
+                    }
+                    if self.peek() != Some('=') {
+                        attributes.push(Attribute::Named(attribute_name, None));
+                        continue;
+                    }
+                    self.bump();
+                    self.skip_whitespace();
+                    let value = match self.peek() {
+                        Some('"') => AttributeValue::Str(self.parse_string()?),
+                        Some('\'') if self.html => AttributeValue::Str(self.parse_single_quoted()?),
+                        Some('{') => AttributeValue::Block(self.parse_block()?),
+                        _ => AttributeValue::Literal(self.parse_literal()?)
+                    };
+                    attributes.push(Attribute::Named(attribute_name, Some(value)));
+                }
+                None => return Err(format!("Unclosed tag `{}`", name))
+            }
+        }
+    }
+
+    // Names are stringified with whitespace around each joining token, such as
+    // "x - image - item" or "svg : path", so glue the pieces back together.
+    fn parse_name(&mut self) -> Result<String, String> {
+        self.skip_whitespace();
+        let mut name = self.parse_word()?;
+
+        loop {
+            let checkpoint = self.pos;
+            self.skip_whitespace();
+            let joiner = match self.peek() {
+                Some('-') => "-",
+                Some('.') => ".",
+                Some(':') if self.source[self.pos..].starts_with("::") => "::",
+                Some(':') => ":",
+                _ => {
+                    self.pos = checkpoint;
+                    return Ok(name);
+                }
+            };
+            self.pos += joiner.len();
+            self.skip_whitespace();
+            name.push_str(joiner);
+            name.push_str(&self.parse_word()?);
+        }
+    }
+
+    fn parse_word(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if !(c.is_alphanumeric() || c == '_') {
+                break;
+            }
+            self.bump();
+        }
+        if start == self.pos {
+            return Err(format!("Expected a name at {}", start));
+        }
+        Ok(self.source[start..self.pos].to_string())
+    }
+
+    fn parse_literal(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        while let Some(c) = self.peek() {
+            if c.is_whitespace() || c == '/' || c == '>' {
+                break;
+            }
+            self.bump();
+        }
+        if start == self.pos {
+            return Err(format!("Expected an attribute value at {}", start));
+        }
+        Ok(self.source[start..self.pos].to_string())
+    }
+
+    // Returns the string literal including its quotes.
+    fn parse_string(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        self.bump();
+        loop {
+            match self.bump() {
+                Some('\\') => {
+                    self.bump();
+                }
+                Some('"') => return Ok(self.source[start..self.pos].to_string()),
+                Some(_) => {}
+                None => return Err(format!("Unterminated string literal at {}", start))
+            }
+        }
+    }
+
+    // Returns a single-quoted HTML attribute value as a string literal.
+    fn parse_single_quoted(&mut self) -> Result<String, String> {
+        let start = self.pos;
+        self.bump();
+        match self.source[self.pos..].find('\'') {
+            Some(end) => {
+                let value = &self.source[self.pos..self.pos + end];
+                self.pos += end + 1;
+                Ok(format!("{:?}", value))
+            }
+            None => Err(format!("Unterminated attribute value at {}", start))
+        }
+    }
+
+    fn flush_text(&self, text: &mut String, nodes: &mut Vec<Node>) {
+        let mut collapsed = text.split_whitespace().collect::<Vec<_>>().join(" ");
+        if self.html && !collapsed.is_empty() {
+            if text.starts_with(char::is_whitespace) {
+                collapsed.insert(0, ' ');
+            }
+            if text.ends_with(char::is_whitespace) {
+                collapsed.push(' ');
+            }
+        } else if self.html && !text.is_empty() {
+            collapsed.push(' ');
+        }
+        if !collapsed.is_empty() {
+            nodes.push(Node::Text(collapsed));
+        }
+        text.clear();
+    }
+
+    fn at_cfg_marker(&self) -> bool {
+        let rest = self.source[self.pos + 1..].trim_left();
+        rest.starts_with('[') && rest[1..].trim_left().starts_with("cfg")
+    }
+
+    // Parses `#[cfg(predicate)]`, returning the predicate.
+    fn parse_cfg_marker(&mut self) -> Result<String, String> {
+        self.expect('#')?;
+        self.expect('[')?;
+        let name = self.parse_name()?;
+        if name != CFG {
+            return Err(format!("Expected `#[cfg(...)]` but found `#[{}`", name));
+        }
+        self.skip_whitespace();
+        if self.peek() != Some('(') {
+            return Err(format!("Expected `(` after `cfg` at {}", self.pos));
+        }
+        let predicate = self.parse_group('(', ')')?;
+        self.expect(']')?;
+        Ok(predicate)
+    }
+
+    // Returns the trimmed contents of a balanced `{ ... }` group.
+    fn parse_block(&mut self) -> Result<String, String> {
+        self.parse_group('{', '}')
+    }
+
+    fn parse_group(&mut self, open: char, close: char) -> Result<String, String> {
+        let start = self.pos;
+        let mut depth = 0;
+
+        loop {
+            match self.peek() {
+                Some(c) if c == open => {
+                    depth += 1;
+                    self.bump();
+                }
+                Some(c) if c == close => {
+                    depth -= 1;
+                    self.bump();
+                    if depth == 0 {
+                        return Ok(self.source[start + 1..self.pos - 1].trim().to_string());
+                    }
+                }
+                Some('"') => {
+                    self.parse_string()?;
+                }
+                Some('\'') => {
+                    self.bump();
+                    // Either a char literal or a lifetime.
+                    let source = self.source;
+                    let rest = &source[self.pos..];
+                    if rest.starts_with('\\') {
+                        self.bump();
+                        self.bump();
+                        while let Some(c) = self.bump() {
+                            if c == '\'' {
+                                break;
+                            }
+                        }
+                    } else if rest.chars().nth(1) == Some('\'') {
+                        self.bump();
+                        self.bump();
+                    }
+                }
+                Some(_) => {
+                    self.bump();
+                }
+                None => return Err(format!("Unbalanced `{}` at {}", open, start))
+            }
+        }
+    }
+}
@@ -0,0 +1,93 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use quote::{Ident, Tokens};
+use regex::Regex;
+
+use css;
+
+// Taking a selector from a stylesheet with a string only fails at runtime, as
+// an empty set of declarations. With the `typed = true,` option, the
+// stylesheet is instead turned into an anonymous struct with one field per
+// class selector, such as `styles.root` for `.root`, so that a misspelled
+// class is a compile error. Dashes in class names become underscores.
+
+// Strict and reserved keywords, which are suffixed with an underscore.
+const KEYWORDS: &[&str] = &[
+    "Self", "_", "abstract", "alignof", "as", "async", "await", "become", "box", "break", "const", "continue", "crate", "do", "dyn", "else",
+    "enum", "extern", "false", "final", "fn", "for", "if", "impl", "in", "let", "loop", "macro", "match", "mod", "move", "mut", "offsetof",
+    "override", "priv", "proc", "pub", "pure", "ref", "return", "self", "sizeof", "static", "struct", "super", "trait", "true", "try",
+    "type", "typeof", "unsafe", "unsized", "use", "virtual", "where", "while", "yield"
+];
+
+pub fn parse_option(value: Option<String>) -> bool {
+    match value.as_ref().map(|value| value.as_str()) {
+        None | Some("false") => false,
+        Some("true") => true,
+        Some(value) => panic!("Expected `true` or `false` for the `typed` option, found `{}`", value)
+    }
+}
+
+// Returns the classes used as a whole selector, in order of appearance.
+pub fn class_names(css: &str) -> Vec<String> {
+    let re_class = Regex::new(r"^\.(?P<name>-?[a-zA-Z_][a-zA-Z0-9_-]*)(?::(?:hover|active|focus|disabled))*$").unwrap();
+    let mut names = vec![];
+
+    for (open, _) in css.match_indices('{') {
+        let start = css::prelude_start(css, 0, open);
+        let prelude = css[start..open].trim();
+        if prelude.starts_with('@') {
+            continue;
+        }
+        for selector in prelude.split(',').map(str::trim) {
+            if let Some(captures) = re_class.captures(selector) {
+                let name = captures["name"].to_string();
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+    }
+
+    names
+}
+
+pub fn expand(stylesheet: &Tokens, class_names: &[String]) -> Tokens {
+    let mut fields: Vec<String> = vec![];
+    for name in class_names {
+        let field = name.trim_left_matches('-').replace('-', "_");
+        let field = if KEYWORDS.contains(&field.as_str()) { format!("{}_", field) } else { field };
+        if fields.contains(&field) {
+            panic!("The `.{}` class conflicts with another class as the `{}` field", name, field);
+        }
+        fields.push(field);
+    }
+
+    let fields = fields.iter().map(|field| Ident::from(field.as_str())).collect::<Vec<_>>();
+    let types = &fields;
+    let values = &fields;
+    let selectors = class_names.iter().map(|name| format!(".{}", name));
+
+    quote! {
+        {
+            #[allow(non_snake_case)]
+            struct __RsxStyles {
+                #(#types: StyleDeclarations,)*
+            }
+
+            #[allow(unused_mut)]
+            let mut __rsx_stylesheet = #stylesheet;
+            __RsxStyles {
+                #(#values: __rsx_stylesheet.take(#selectors),)*
+            }
+        }
+    }
+}
@@ -0,0 +1,198 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+use std::collections::HashMap;
+
+use css;
+
+// Custom properties are resolved at compile time, since the servo parser can't
+// convert them into style declarations. Properties declared in `:root` rules
+// are global, and properties declared in other rules only apply to them. Every
+// `var(--name, fallback)` is replaced by its value, and custom property
+// declarations are blanked out. Line breaks are kept, so that diagnostics
+// still point at the right line.
+
+pub type Variables = HashMap<String, String>;
+
+const ROOT_SELECTOR: &str = ":root";
+
+pub fn collect(css: &str) -> Variables {
+    let mut variables = Variables::new();
+    for_each_rule(css, 0, &mut |prelude, body, _| {
+        if prelude.trim() == ROOT_SELECTOR {
+            for (name, value) in custom_properties(body) {
+                variables.insert(name, value);
+            }
+        }
+    });
+    variables
+}
+
+pub fn substitute(css: &str, variables: &Variables) -> String {
+    let mut substituted = css.to_string();
+    let mut replacements = vec![];
+
+    for_each_rule(css, 0, &mut |prelude, body, body_start| {
+        let mut scoped = variables.clone();
+        for (name, value) in custom_properties(body) {
+            scoped.insert(name, value);
+        }
+
+        let only_custom_properties = split_declarations(body).iter().all(|&(_, declaration)| {
+            declaration.trim().is_empty() || declaration.trim().starts_with("--")
+        });
+        if prelude.trim() == ROOT_SELECTOR && only_custom_properties {
+            let prelude_start = body_start - 1 - prelude.len();
+            replacements.push((prelude_start, body_start + body.len() + 1, None));
+            return;
+        }
+
+        for (offset, declaration) in split_declarations(body) {
+            let start = body_start + offset;
+            let end = start + declaration.len();
+            if declaration.trim().starts_with("--") {
+                replacements.push((start, end, None));
+            } else if declaration.contains("var(") {
+                let resolved = resolve(declaration, &scoped, &mut vec![]).unwrap_or_else(|error| panic!("{}", error));
+                replacements.push((start, end, Some(resolved)));
+            }
+        }
+    });
+
+    // Replace from the end, so that earlier offsets stay valid.
+    replacements.sort_by(|a, b| b.0.cmp(&a.0));
+    for (start, end, replacement) in replacements {
+        let replacement = replacement.unwrap_or_else(|| blank(&css[start..end]));
+        substituted = format!("{}{}{}", &substituted[..start], replacement, &substituted[end..]);
+    }
+
+    substituted
+}
+
+fn resolve(value: &str, variables: &Variables, stack: &mut Vec<String>) -> Result<String, String> {
+    let mut resolved = String::new();
+    let mut rest = value;
+
+    while let Some(start) = rest.find("var(") {
+        resolved.push_str(&rest[..start]);
+        let close = css::matching_brace(rest, start + 3).ok_or_else(|| format!("Unbalanced parentheses in `{}`", value))?;
+        let arguments = &rest[start + 4..close];
+        let (name, fallback) = match top_level_comma(arguments) {
+            Some(comma) => (arguments[..comma].trim(), Some(arguments[comma + 1..].trim())),
+            None => (arguments.trim(), None)
+        };
+
+        let substitution = match (variables.get(name), fallback) {
+            (Some(variable), _) => {
+                if stack.iter().any(|entry| entry == name) {
+                    return Err(format!(
+                        "Cycle between custom properties: {} -> {}",
+                        stack.join(" -> "),
+                        name
+                    ));
+                }
+                stack.push(name.to_string());
+                let substitution = resolve(variable, variables, stack)?;
+                stack.pop();
+                substitution
+            }
+            (None, Some(fallback)) => resolve(fallback, variables, stack)?,
+            (None, None) => return Err(format!("Undefined custom property `{}` without a fallback", name))
+        };
+
+        resolved.push_str(&substitution);
+        rest = &rest[close + 1..];
+    }
+
+    resolved.push_str(rest);
+    Ok(resolved)
+}
+
+// Calls `f` with the prelude, body and body offset of every rule, including
+// the ones nested in at-rules.
+fn for_each_rule(css: &str, offset: usize, f: &mut FnMut(&str, &str, usize)) {
+    let mut pos = 0;
+
+    while let Some(open) = css[pos..].find('{').map(|open| pos + open) {
+        let statement_start = css::prelude_start(css, pos, open);
+        let prelude = &css[statement_start..open];
+        let close = match css::matching_brace(css, open) {
+            Some(close) => close,
+            None => return
+        };
+        let body = &css[open + 1..close];
+
+        if prelude.trim().starts_with('@') {
+            for_each_rule(body, offset + open + 1, f);
+        } else {
+            f(prelude, body, offset + open + 1);
+        }
+        pos = close + 1;
+    }
+}
+
+fn custom_properties(body: &str) -> Vec<(String, String)> {
+    split_declarations(body)
+        .into_iter()
+        .filter_map(|(_, declaration)| {
+            let declaration = declaration.trim();
+            let colon = declaration.find(':')?;
+            let name = declaration[..colon].trim();
+            if name.starts_with("--") {
+                Some((name.to_string(), declaration[colon + 1..].trim().to_string()))
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+// Returns the offset and text of each declaration, including the terminating
+// semicolon if there's one.
+fn split_declarations(body: &str) -> Vec<(usize, &str)> {
+    let mut declarations = vec![];
+    let mut start = 0;
+    let mut depth = 0;
+
+    for (index, c) in body.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ';' if depth == 0 => {
+                declarations.push((start, &body[start..index + 1]));
+                start = index + 1;
+            }
+            _ => {}
+        }
+    }
+
+    if !body[start..].trim().is_empty() {
+        declarations.push((start, &body[start..]));
+    }
+    declarations
+}
+
+fn top_level_comma(source: &str) -> Option<usize> {
+    let mut depth = 0;
+    for (index, c) in source.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => depth -= 1,
+            ',' if depth == 0 => return Some(index),
+            _ => {}
+        }
+    }
+    None
+}
+
+fn blank(source: &str) -> String {
+    source.chars().map(|c| if c == '\n' { '\n' } else { ' ' }).collect()
+}
@@ -0,0 +1,82 @@
+/*
+Copyright 2016 Mozilla
+Licensed under the Apache License, Version 2.0 (the "License"); you may not use
+this file except in compliance with the License. You may obtain a copy of the
+License at http://www.apache.org/licenses/LICENSE-2.0
+Unless required by applicable law or agreed to in writing, software distributed
+under the License is distributed on an "AS IS" BASIS, WITHOUT WARRANTIES OR
+CONDITIONS OF ANY KIND, either express or implied. See the License for the
+specific language governing permissions and limitations under the License.
+*/
+
+#![feature(proc_macro)]
+
+// Neither `#[macro_use]` on rsx-dom nor a glob import of its types here, so
+// the expansions have to name everything they use.
+
+extern crate rsx;
+extern crate rsx_dom;
+extern crate rsx_layout;
+extern crate rsx_stylesheet;
+
+mod primitives {
+    pub use rsx_dom::types;
+}
+
+use rsx::rsx;
+use rsx_dom::types;
+use rsx_stylesheet::types::{ComputedStyles, StyleDeclarations};
+
+type LayoutNode = rsx_layout::types::LayoutNode<StyleDeclarations, ComputedStyles, (), types::DOMText>;
+type DOMTree = types::DOMTree<(), StyleDeclarations, ComputedStyles, LayoutNode>;
+type DOMNode = types::DOMNode<(), StyleDeclarations, ComputedStyles, LayoutNode>;
+
+fn render_text() -> DOMTree {
+    rsx! {
+        <text>Hello world!</text>
+    }
+}
+
+fn expected_text() -> DOMNode {
+    DOMNode::from((
+        types::DOMTagName::from(types::KnownElementName::Text),
+        vec![],
+        vec![DOMNode::from("Hello world !")]
+    ))
+}
+
+#[test]
+fn test_rsx_without_imports() {
+    // The `DOMTree` alias in the code block is the local one.
+    let tree: DOMTree = rsx! {
+        <view>
+            { let child: DOMTree = render_text(); child }
+        </view>
+    };
+
+    let expected = DOMTree::from(DOMNode::from((
+        types::DOMTagName::from(types::KnownElementName::View),
+        vec![],
+        vec![expected_text()]
+    )));
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_reexported_crate_path() {
+    let tree: DOMTree = rsx! {
+        crate = ::primitives,
+        <text>Hello world!</text>
+    };
+
+    let expected = DOMTree::from(expected_text());
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
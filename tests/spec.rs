@@ -21,13 +21,15 @@ extern crate rsx_layout;
 extern crate rsx_shared;
 extern crate rsx_stylesheet;
 
-use rsx::{css, load_font, load_image, rsx};
+use rsx::{component, css, define_element, html, load_font, load_image, markdown, rsx, rsx_context, rsx_html, rsx_hydrate, t};
 use rsx_dom::types::*;
 use rsx_fonts::types::*;
 use rsx_images::types::*;
 use rsx_shared::traits::*;
 use rsx_stylesheet::types::*;
 
+rsx_context!();
+
 type LayoutNode = rsx_layout::types::LayoutNode<StyleDeclarations, ComputedStyles, (), DOMText>;
 type DOMTree = rsx_dom::types::DOMTree<(), StyleDeclarations, ComputedStyles, LayoutNode>;
 type DOMNode = rsx_dom::types::DOMNode<(), StyleDeclarations, ComputedStyles, LayoutNode>;
@@ -218,6 +220,277 @@ fn test_css_to_stylesheet_3() {
     assert_eq!(stylesheet, expected);
 }
 
+#[test]
+fn test_css_to_stylesheet_spacing() {
+    let stylesheet = css! {
+        .col-md-4 > .item-2, .view .flex-row-reverse {
+            flex-direction: row-reverse;
+            margin-left: 10px;
+        }
+    };
+
+    let expected = Stylesheet::from(InlineRules::from_vec(vec![
+        StyleRule {
+            selectors: StyleSelectors(InlineSelectors::from_vec(vec![
+                StyleSelector::from(".col-md-4 > .item-2"),
+                StyleSelector::from(".view .flex-row-reverse"),
+            ])),
+            declarations: StyleDeclarations(InlineDeclarations::from_vec(vec![
+                StyleDeclaration::Layout(FlexStyle::FlexDirection(FlexDirection::RowReverse)),
+                StyleDeclaration::Layout(FlexStyle::MarginLeft(StyleUnit::Point(10f32.into()))),
+            ]))
+        },
+    ]));
+
+    assert_eq!(stylesheet, expected);
+}
+
+#[test]
+fn test_css_relative_to_caller() {
+    let stylesheet = css!("./fixtures/test_2.css");
+    let expected = css!("tests/fixtures/test_2.css");
+
+    assert_eq!(stylesheet, expected);
+}
+
+#[test]
+fn test_css_import() {
+    let stylesheet = css!("tests/fixtures/imports.css");
+
+    let expected = css! {
+        .foo {
+            margin: 0 auto;
+            padding: 10px;
+        }
+        .bar {
+            padding: 10px;
+        }
+    };
+
+    assert_eq!(stylesheet, expected);
+}
+
+#[test]
+fn test_css_lint_allow() {
+    let stylesheet = css! {
+        lint = allow,
+        .foo {
+            unknown-property: 1px;
+            width: 10px;
+        }
+    };
+
+    let expected = css! {
+        .foo {
+            width: 10px;
+        }
+    };
+
+    assert_eq!(stylesheet, expected);
+}
+
+#[test]
+fn test_css_custom_properties() {
+    let stylesheet = css! {
+        :root {
+            --size: 10px;
+            --width: var(--size);
+        }
+        .foo {
+            width: var(--width);
+            height: var(--missing, 20px);
+        }
+    };
+
+    let expected = css! {
+        .foo {
+            width: 10px;
+            height: 20px;
+        }
+    };
+
+    assert_eq!(stylesheet, expected);
+}
+
+#[test]
+fn test_css_nesting() {
+    let stylesheet = css! {
+        .card {
+            padding: 4px;
+            & .title {
+                flex-grow: 1;
+            }
+            .icon, &.wide {
+                width: 10px;
+            }
+            height: 20px;
+        }
+    };
+
+    let expected = css! {
+        .card {
+            padding: 4px;
+        }
+        .card .title {
+            flex-grow: 1;
+        }
+        .card .icon, .card.wide {
+            width: 10px;
+        }
+        .card {
+            height: 20px;
+        }
+    };
+
+    assert_eq!(stylesheet, expected);
+}
+
+#[test]
+fn test_css_media_queries() {
+    let styles = css! {
+        .foo {
+            width: 10px;
+        }
+        @media (min-width: 600px) {
+            .foo {
+                width: 20px;
+            }
+        }
+    };
+
+    let narrow = css! {
+        .foo {
+            width: 10px;
+        }
+    };
+
+    let wide = css! {
+        .foo {
+            width: 10px;
+        }
+        .foo {
+            width: 20px;
+        }
+    };
+
+    assert_eq!(styles.evaluate((400.0, 300.0)), narrow);
+    assert_eq!(styles.evaluate((800.0, 600.0)), wide);
+}
+
+#[test]
+fn test_css_many_media_queries() {
+    let styles = css! {
+        @media (min-width: 100px) { .foo { width: 1px; } }
+        @media (min-width: 200px) { .foo { width: 2px; } }
+        @media (min-width: 300px) { .foo { width: 3px; } }
+        @media (min-width: 400px) { .foo { width: 4px; } }
+        @media (min-width: 500px) { .foo { width: 5px; } }
+        @media (orientation: portrait) { .foo { height: 10px; } }
+    };
+
+    let expected = css! {
+        .foo { width: 1px; }
+        .foo { width: 2px; }
+        .foo { width: 3px; }
+        .foo { width: 4px; }
+        .foo { width: 5px; }
+    };
+
+    assert_eq!(styles.evaluate((600.0, 300.0)), expected);
+}
+
+#[test]
+fn test_css_states() {
+    let styles = css! {
+        .button {
+            width: 10px;
+        }
+        .button:hover {
+            height: 20px;
+        }
+        .button:active {
+            flex-grow: 1;
+        }
+    };
+
+    let mut idle = css! {
+        .button {
+            width: 10px;
+        }
+    };
+
+    let mut hovered = css! {
+        .button {
+            width: 10px;
+            height: 20px;
+        }
+    };
+
+    let mut pressed = css! {
+        .button {
+            width: 10px;
+            height: 20px;
+            flex-grow: 1;
+        }
+    };
+
+    assert_eq!(styles.declarations_for(".button", 0), idle.take(".button"));
+    assert_eq!(styles.declarations_for(".button", 1), hovered.take(".button"));
+    assert_eq!(styles.declarations_for(".button", 3), pressed.take(".button"));
+    assert_eq!(styles.declarations_for(".button", 8), styles.declarations_for(".button", 0));
+}
+
+#[test]
+fn test_css_states_2() {
+    let mut styles: rsx_context::ConditionalStylesheet = css! {
+        .button {
+            width: 10px;
+        }
+        .button:hover {
+            height: 20px;
+        }
+        @media (min-width: 600px) {
+            .button {
+                flex-grow: 1;
+            }
+        }
+    };
+
+    let mut idle = css! {
+        .button {
+            width: 10px;
+        }
+    };
+
+    assert_eq!(styles.take(".button"), idle.take(".button"));
+}
+
+#[test]
+fn test_css_typed() {
+    let styles = css! {
+        typed = true,
+        .root {
+            width: 10px;
+        }
+        .card-title, .icon {
+            height: 20px;
+        }
+    };
+
+    let mut stylesheet = css! {
+        .root {
+            width: 10px;
+        }
+        .card-title, .icon {
+            height: 20px;
+        }
+    };
+
+    assert_eq!(styles.root, stylesheet.take(".root"));
+    assert_eq!(styles.card_title, stylesheet.take(".card-title"));
+    assert_eq!(styles.icon, stylesheet.take(".icon"));
+}
+
 #[test]
 fn test_css_to_stylesheet_4() {
     let stylesheet = css!("tests/fixtures/test_1.css");
@@ -382,6 +655,14 @@ fn test_rsx_x_1() {
 
 #[test]
 fn test_rsx_x_2() {
+    define_element! {
+        x-image-display-item {}
+    }
+
+    define_element! {
+        x-button-display-item {}
+    }
+
     let tree = rsx! {
         <x-image-display-item>
             <x-button-display-item>
@@ -410,6 +691,89 @@ fn test_rsx_x_2() {
     );
 }
 
+#[test]
+fn test_rsx_x_3() {
+    define_element! {
+        x-badge {
+            label: &'static str,
+            selected: bool
+        }
+        style {
+            width: 20px;
+            flex-grow: 1;
+        }
+    }
+
+    let tree = rsx! {
+        <x-badge label="Inbox" selected={true} />
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from("x-badge"),
+            vec![
+                DOMAttribute::from((
+                    DOMAttributeName::from("label"),
+                    DOMAttributeValue::from("Inbox")
+                )),
+                DOMAttribute::from((
+                    DOMAttributeName::from("selected"),
+                    DOMAttributeValue::from(true)
+                )),
+                DOMAttribute::from((
+                    DOMAttributeName::from(KnownAttributeName::Style),
+                    DOMAttributeValue::from(StyleDeclarations(InlineDeclarations::from_vec(vec![
+                        StyleDeclaration::Layout(FlexStyle::Width(StyleUnit::Point(20.0.into()))),
+                        StyleDeclaration::Layout(FlexStyle::FlexGrow(1.0.into())),
+                    ])))
+                )),
+            ]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_x_4() {
+    define_element! {
+        x-label {
+            text: &'static str
+        }
+    }
+
+    fn make_label(name: String) -> &'static str {
+        if name.is_empty() { "Empty" } else { "Inbox" }
+    }
+
+    let name = "inbox".to_string();
+
+    let tree = rsx! {
+        <x-label text={make_label(name)} />
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from("x-label"),
+            vec![
+                DOMAttribute::from((
+                    DOMAttributeName::from("text"),
+                    DOMAttributeValue::from("Inbox")
+                )),
+            ],
+            vec![]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
 #[test]
 fn test_example_1() {
     let mut stylesheet = css! {
@@ -636,6 +1000,537 @@ fn test_example_2() {
     );
 }
 
+#[test]
+fn test_rsx_format_text() {
+    struct User {
+        name: String
+    }
+
+    let user = User {
+        name: "world".to_string()
+    };
+    let count = 7;
+
+    let tree = rsx! {
+        <text>
+            Hello {user.name}, you have {count:>3} messages
+        </text>
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::Text),
+            vec![],
+            vec![DOMNode::from("Hello world , you have   7 messages")]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_t() {
+    let name = "world";
+    let count = 3;
+
+    assert_eq!(t!("greeting", name = name, locale = "en"), "Hello world!");
+    assert_eq!(t!("greeting", name = name, locale = "fr-CA"), "Bonjour world !");
+    assert_eq!(t!("inbox", count = count, locale = "fr"), "Vous avez 3 nouveaux messages");
+}
+
+#[test]
+fn test_t_active_locale() {
+    let name = "world";
+
+    assert_eq!(t!("greeting", name = name), "Hello world!");
+    rsx_context::set_locale("fr");
+    assert_eq!(t!("greeting", name = name), "Bonjour world !");
+    rsx_context::set_locale("en-GB");
+    assert_eq!(t!("greeting", name = name), "Hello world!");
+}
+
+#[test]
+fn test_rsx_t() {
+    let name = "world";
+
+    let tree = rsx! {
+        <text>
+            <t id="greeting" name={name} locale="en" />
+        </text>
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::Text),
+            vec![],
+            vec![DOMNode::from("Hello world!")]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_crate_path() {
+    let tree: DOMTree = rsx! {
+        crate = ::rsx_dom,
+        <text>
+            Hello world!
+        </text>
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::Text),
+            vec![],
+            vec![DOMNode::from("Hello world !")]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_context() {
+    use rsx_context::use_context;
+
+    #[derive(Clone)]
+    struct Theme {
+        accent: String
+    }
+
+    fn render_button() -> DOMTree {
+        let theme = use_context::<Theme>().unwrap();
+        rsx! {
+            <text color={theme.accent}>
+                Press me
+            </text>
+        }
+    }
+
+    let theme = Theme {
+        accent: "red".to_string()
+    };
+
+    let tree = rsx! {
+        <view>
+            <Provider value={theme}>
+                { render_button() }
+            </Provider>
+        </view>
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::View),
+            vec![],
+            vec![
+                DOMNode::from((
+                    DOMTagName::from(KnownElementName::Text),
+                    vec![
+                        DOMAttribute::from((
+                            DOMAttributeName::from("color"),
+                            DOMAttributeValue::from("red")
+                        )),
+                    ],
+                    vec![DOMNode::from("Press me")]
+                )),
+            ]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+    assert!(use_context::<Theme>().is_none());
+}
+
+#[test]
+fn test_rsx_memo_component() {
+    thread_local! {
+        static BUILDS: ::std::cell::Cell<usize> = ::std::cell::Cell::new(0);
+    }
+
+    #[derive(PartialEq, Clone)]
+    struct PanelProps {
+        title: String
+    }
+
+    #[component(memo)]
+    fn render_panel(props: &PanelProps) -> DOMTree {
+        BUILDS.with(|builds| builds.set(builds.get() + 1));
+        rsx! {
+            <text>
+                { props.title.as_str() }
+            </text>
+        }
+    }
+
+    let props = PanelProps {
+        title: "Settings".to_string()
+    };
+
+    let first = render_panel(&props);
+    let second = render_panel(&props.clone());
+    assert_eq!(BUILDS.with(|builds| builds.get()), 1);
+
+    assert_eq!(
+        first.root().traverse_iter().collect::<Vec<_>>(),
+        second.root().traverse_iter().collect::<Vec<_>>()
+    );
+
+    render_panel(&PanelProps {
+        title: "Advanced".to_string()
+    });
+    assert_eq!(BUILDS.with(|builds| builds.get()), 2);
+}
+
+#[test]
+fn test_html() {
+    let tree = html!("<p onclick=\"steal()\">Some <b>bold</b> text<script>alert(1)</script><br><img src='a.png' alt='A picture'></p>");
+
+    let expected = rsx! {
+        <p>
+            { "Some " }
+            <b>{ "bold" }</b>
+            { " text" }
+            <br />
+            <img src="a.png" alt="A picture" />
+        </p>
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_raw() {
+    let tree = rsx! {
+        <view>
+            <raw html="<a href=\"https://example.com\">Fish &amp; chips</a>" />
+        </view>
+    };
+
+    let expected = rsx! {
+        <view>
+            <a href="https://example.com">{ "Fish & chips" }</a>
+        </view>
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_cfg() {
+    let tree = rsx! {
+        <view>
+            <text cfg(not(any()))>Debug overlay</text>
+            #[cfg(all())]
+            <text #[cfg(not(all()))] color="red">Hello world!</text>
+        </view>
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::View),
+            vec![],
+            vec![
+                DOMNode::from((
+                    DOMTagName::from(KnownElementName::Text),
+                    vec![],
+                    vec![DOMNode::from("Hello world !")]
+                )),
+            ]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_cfg_2() {
+    let tree = rsx! {
+        <view #[cfg(all())] title="a" #[cfg(not(all()))] lang="b" #[cfg(all())] alt="c">
+            <text cfg(all())>One</text>
+            <text cfg(not(all()))>Two</text>
+            <text cfg(all())>Three</text>
+            #[cfg(not(all()))]
+            <text>Four</text>
+        </view>
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::View),
+            vec![
+                DOMAttribute::from((
+                    DOMAttributeName::from("title"),
+                    DOMAttributeValue::from("a")
+                )),
+                DOMAttribute::from((
+                    DOMAttributeName::from("alt"),
+                    DOMAttributeValue::from("c")
+                )),
+            ],
+            vec![
+                DOMNode::from((
+                    DOMTagName::from(KnownElementName::Text),
+                    vec![],
+                    vec![DOMNode::from("One")]
+                )),
+                DOMNode::from((
+                    DOMTagName::from(KnownElementName::Text),
+                    vec![],
+                    vec![DOMNode::from("Three")]
+                )),
+            ]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_class_and_style_lists() {
+    let mut stylesheet = css! {
+        .base {
+            width: 100px;
+            flex-grow: 1;
+        }
+        .overrides {
+            width: 50px;
+        }
+    };
+
+    let selected = true;
+    let disabled = false;
+
+    let tree = rsx! {
+        <view
+            class={["card", if selected { Some("card-selected") } else { None }, if disabled { Some("card-disabled") } else { None }]}
+            style={[stylesheet.take(".base"), stylesheet.take(".overrides")]}
+        />
+    };
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::View),
+            vec![
+                DOMAttribute::from((
+                    DOMAttributeName::from("class"),
+                    DOMAttributeValue::from("card card-selected")
+                )),
+                DOMAttribute::from((
+                    DOMAttributeName::from(KnownAttributeName::Style),
+                    DOMAttributeValue::from(StyleDeclarations(InlineDeclarations::from_vec(vec![
+                        StyleDeclaration::Layout(FlexStyle::FlexGrow(1.0.into())),
+                        StyleDeclaration::Layout(FlexStyle::Width(StyleUnit::Point(50.0.into()))),
+                    ])))
+                )),
+            ]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_generic_component() {
+    struct Label<T> {
+        value: T
+    }
+
+    impl<T: ToString> Label<T> {
+        fn render(self) -> DOMTree {
+            let text = self.value.to_string();
+            rsx! {
+                <text>{ text }</text>
+            }
+        }
+    }
+
+    let tree = rsx! {
+        <view>
+            <Label<_> value={7} />
+            <Label<&str> value="seven" />
+        </view>
+    };
+
+    let expected = rsx! {
+        <view>
+            <text>{ "7" }</text>
+            <text>{ "seven" }</text>
+        </view>
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_markdown() {
+    let tree = markdown!(
+        inline = "# Help\n\nSome *emphasis* here.",
+        root = "view",
+        heading = "text",
+        paragraph = "text",
+        emphasis = "text"
+    );
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::View),
+            vec![],
+            vec![
+                DOMNode::from((
+                    DOMTagName::from(KnownElementName::Text),
+                    vec![],
+                    vec![DOMNode::from("Help")]
+                )),
+                DOMNode::from((
+                    DOMTagName::from(KnownElementName::Text),
+                    vec![],
+                    vec![
+                        DOMNode::from("Some "),
+                        DOMNode::from((
+                            DOMTagName::from(KnownElementName::Text),
+                            vec![],
+                            vec![DOMNode::from("emphasis")]
+                        )),
+                        DOMNode::from(" here."),
+                    ]
+                )),
+            ]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn test_rsx_html_1() {
+    let name = "<world>";
+
+    let html = rsx_html! {
+        <view class="root">
+            <text>Hello {name}!</text>
+            { rsx_html! { <image src="..." /> } }
+        </view>
+    };
+
+    assert_eq!(
+        html,
+        "<view class=\"root\"><text>Hello &lt;world&gt; !</text><image src=\"...\"></image></view>"
+    );
+}
+
+#[test]
+fn test_rsx_html_2() {
+    let mut stylesheet = css! {
+        .foo {
+            width: 10px;
+            margin: 0 auto;
+            background-color: red;
+            opacity: 0.5;
+        }
+    };
+
+    let html = rsx_html! {
+        <view title={"A & B"} style={stylesheet.take(".foo")}>
+            <text style={"color: red"}>Hello</text>
+        </view>
+    };
+
+    assert_eq!(
+        html,
+        "<view title=\"A &amp; B\" style=\"width: 10px; margin-top: 0px; margin-right: auto; margin-bottom: 0px; margin-left: auto; \
+         background-color: rgba(255, 0, 0, 1); opacity: 0.5\"><text style=\"color: red\">Hello</text></view>"
+    );
+}
+
+#[test]
+fn test_rsx_hydrate() {
+    #[component(hydrate)]
+    fn render(name: &str, class: &str) -> String {
+        rsx_html!(hydrate,
+            <view class={class}>
+                <text>Hello {name}!</text>
+            </view>
+        )
+    }
+
+    let html = render("<world>", "root");
+
+    assert_eq!(
+        html,
+        "<view class=\"root\" data-rsx-id=\"0\"><text data-rsx-id=\"1\"><!--rsx:0-->Hello &lt;world&gt; !<!--/rsx:0--></text></view>"
+    );
+
+    let tree = rsx_hydrate!(html, render);
+
+    let expected = fragment! {
+        DOMNode::from((
+            DOMTagName::from(KnownElementName::View),
+            vec![
+                DOMAttribute::from((
+                    DOMAttributeName::from("class"),
+                    DOMAttributeValue::from("root")
+                )),
+                DOMAttribute::from((
+                    DOMAttributeName::from("data-rsx-id"),
+                    DOMAttributeValue::from("0")
+                )),
+            ],
+            vec![
+                DOMNode::from((
+                    DOMTagName::from(KnownElementName::Text),
+                    vec![
+                        DOMAttribute::from((
+                            DOMAttributeName::from("data-rsx-id"),
+                            DOMAttributeValue::from("1")
+                        )),
+                    ],
+                    vec![DOMNode::from("Hello <world> !")]
+                )),
+            ]
+        ))
+    };
+
+    assert_eq!(
+        tree.root().traverse_iter().collect::<Vec<_>>(),
+        expected.root().traverse_iter().collect::<Vec<_>>()
+    );
+}
+
 #[test]
 fn test_image_load() {
     let image = load_image!("tests/fixtures/Quantum.png");